@@ -0,0 +1,33 @@
+//! Compares reading the time via `enable`, which opens the timestamp file by path on every read,
+//! with `enable_file`, which reads the already opened file from the beginning.
+//!
+//! Run it via `cargo bench --bench enable_file`, which prints the cost per `unix_time` call of
+//! both. The gap is the cost of the syscalls to resolve the path, open and close the file.
+
+#[cfg(not(disable_faketime))]
+fn main() {
+    use std::fs::File;
+    use std::time::{Duration, Instant};
+
+    const READS: u32 = 20_000;
+
+    fn per_read() -> Duration {
+        let start = Instant::now();
+        for _ in 0..READS {
+            std::hint::black_box(faketime::unix_time());
+        }
+        start.elapsed() / READS
+    }
+
+    let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+    faketime::enable(&faketime_file);
+    let by_path = per_read();
+    faketime::enable_file(File::open(&faketime_file).expect("open faketime file"));
+    let by_handle = per_read();
+
+    println!("enable:      {:?}/read", by_path);
+    println!("enable_file: {:?}/read", by_handle);
+}
+
+#[cfg(disable_faketime)]
+fn main() {}
@@ -0,0 +1,48 @@
+//! Measures reading the clock installed via `install_as_global_default` from many threads, and
+//! compares it with reading a clock behind a `Mutex`.
+//!
+//! Run it via `cargo bench --bench global_clock`. The amortized cost per read of the installed
+//! clock stays flat as the number of reader threads grows, because reading it does not take any
+//! lock, while the threads contend for the `Mutex`.
+
+#[cfg(not(disable_faketime))]
+fn main() {
+    use faketime::clock::{Clock, FakeClock};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const READS: u32 = 200_000;
+
+    /// Reads via `read` in `threads` threads at the same time, and returns the wall time divided
+    /// by the total number of reads.
+    fn per_read<F: Fn() -> Duration + Sync>(threads: u32, read: F) -> Duration {
+        let start = Instant::now();
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    for _ in 0..READS {
+                        std::hint::black_box(read());
+                    }
+                });
+            }
+        });
+        start.elapsed() / (READS * threads)
+    }
+
+    let clock: Arc<dyn Clock + Send + Sync> = Arc::new(FakeClock::new(123_456));
+    faketime::install_as_global_default(Arc::clone(&clock));
+    let locked = Mutex::new(clock);
+
+    for threads in [1, 2, 4, 8, 16] {
+        let global = per_read(threads, faketime::unix_time);
+        let mutex = per_read(threads, || locked.lock().expect("lock clock").unix_time());
+        println!(
+            "{:>2} threads: global clock {:?}/read, mutex {:?}/read",
+            threads, global, mutex
+        );
+    }
+}
+
+#[cfg(disable_faketime)]
+fn main() {}
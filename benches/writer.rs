@@ -0,0 +1,36 @@
+//! Compares `write_millis`, which creates a new temporary file for every write, with `Writer`,
+//! which reuses the same temporary file path.
+//!
+//! Run it via `cargo bench --bench writer`, which prints the cost per write of both. The gap,
+//! if any, is the cost of reserving a temporary file with a new random name on every write,
+//! which depends on the file system.
+
+#[cfg(not(disable_faketime))]
+fn main() {
+    use std::time::Instant;
+
+    const WRITES: u64 = 2_000;
+
+    let tempdir = tempfile::tempdir().expect("create tempdir");
+    let faketime_file = tempdir.path().join("faketime");
+
+    let start = Instant::now();
+    for millis in 0..WRITES {
+        faketime::write_millis(&faketime_file, millis).expect("write millis");
+    }
+    let write_millis = start.elapsed();
+
+    let mut writer = faketime::Writer::new(&faketime_file).expect("create writer");
+    let start = Instant::now();
+    for millis in 0..WRITES {
+        writer.set(millis).expect("write millis");
+    }
+    let writer_set = start.elapsed();
+
+    let per_write = |elapsed: std::time::Duration| elapsed.as_nanos() / u128::from(WRITES);
+    println!("write_millis: {} ns/write", per_write(write_millis));
+    println!("Writer::set:  {} ns/write", per_write(writer_set));
+}
+
+#[cfg(disable_faketime)]
+fn main() {}
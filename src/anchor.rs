@@ -0,0 +1,118 @@
+//! Provides `FakeAnchor`, which measures the elapsed time across the faketime settings changes.
+
+use std::time::Duration;
+
+/// A snapshot of the faked time, and the faketime settings of current thread which are active
+/// when the snapshot is captured.
+///
+/// As long as the settings are kept, the anchor measures the elapsed time via `unix_time` like
+/// `FakeStopwatch`: it does not advance while the time is frozen, and it follows `set_millis` and
+/// `advance`.
+///
+/// Once the settings are changed, for example, the time is frozen via `enable_millis`, or is
+/// unfrozen via `disable`, the faked times before and after the change are not comparable. The
+/// anchor then falls back to the elapsed time of the system monotonic clock since it was
+/// captured, so it never jumps by the distance between the two timelines.
+///
+/// The elapsed time is saturated at zero in both cases.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_000);
+/// let anchor = faketime::anchor();
+/// faketime::advance(Duration::from_secs(2)).expect("advance");
+/// assert_eq!(Duration::from_secs(2), anchor.elapsed());
+///
+/// // The time of the real clock is decades after the frozen time, but it is not counted.
+/// faketime::disable();
+/// assert!(anchor.elapsed() < Duration::from_secs(60));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FakeAnchor {
+    time: Duration,
+    monotonic: Duration,
+    epoch: u64,
+}
+
+/// Captures an anchor of current thread, see `FakeAnchor`.
+pub fn anchor() -> FakeAnchor {
+    FakeAnchor {
+        time: crate::unix_time(),
+        monotonic: crate::system::monotonic(),
+        epoch: mode_epoch(),
+    }
+}
+
+impl FakeAnchor {
+    /// Gets the faked time when the anchor was captured.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Gets the elapsed time since the anchor was captured.
+    pub fn elapsed(&self) -> Duration {
+        // Reads the time first, which may auto-detect the settings again.
+        let now = crate::unix_time();
+        if mode_epoch() == self.epoch {
+            now.saturating_sub(self.time)
+        } else {
+            crate::system::monotonic().saturating_sub(self.monotonic)
+        }
+    }
+}
+
+#[cfg(not(disable_faketime))]
+fn mode_epoch() -> u64 {
+    crate::faketime::mode_epoch()
+}
+
+#[cfg(disable_faketime)]
+fn mode_epoch() -> u64 {
+    0
+}
+
+#[cfg(all(test, not(disable_faketime)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_in_same_mode() {
+        crate::enable_millis(1_000);
+        let anchor = anchor();
+        assert_eq!(Duration::from_secs(1), anchor.time());
+        assert_eq!(Duration::from_secs(0), anchor.elapsed());
+        crate::advance(Duration::from_millis(1_500)).expect("advance");
+        assert_eq!(Duration::from_millis(1_500), anchor.elapsed());
+        crate::set_millis(0).expect("set millis");
+        assert_eq!(Duration::from_secs(0), anchor.elapsed());
+    }
+
+    #[test]
+    fn test_anchor_across_mode_changes() {
+        crate::enable_millis(1_000);
+        let anchor = anchor();
+        crate::disable();
+        assert!(anchor.elapsed() < Duration::from_secs(60));
+        crate::enable_millis(1_000);
+        assert!(anchor.elapsed() < Duration::from_secs(60));
+
+        crate::disable();
+        let anchor = super::anchor();
+        crate::enable_millis(0);
+        assert!(anchor.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_anchor_after_scoped_mode() {
+        crate::disable();
+        let anchor = anchor();
+        crate::with_frozen(1_000, || {
+            assert!(anchor.elapsed() < Duration::from_secs(60));
+        });
+        assert!(anchor.elapsed() < Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,101 @@
+//! Strategies to generate fake clocks in property tests, enabled via the cargo feature
+//! `proptest`.
+
+use proptest::collection::{vec, SizeRange};
+use proptest::prelude::*;
+
+/// Milliseconds since *UNIX EPOCH* of 2000-01-01T00:00:00Z.
+const Y2K_MILLIS: u64 = 946_684_800_000;
+/// Milliseconds since *UNIX EPOCH* of 2100-01-01T00:00:00Z.
+const Y2100_MILLIS: u64 = 4_102_444_800_000;
+
+/// A fake clock frozen at `millis` since *UNIX EPOCH*, which implements `proptest::Arbitrary`.
+///
+/// The generated time covers the boundary values `0` and `u64::MAX`, the typical epochs, and
+/// arbitrary values.
+///
+/// ```
+/// use faketime::FaketimeConfig;
+/// use proptest::prelude::*;
+///
+/// proptest!(|(config: FaketimeConfig)| {
+///     config.enable();
+///     prop_assert_eq!(config.millis, faketime::unix_time_as_millis());
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FaketimeConfig {
+    /// The frozen time in milliseconds since *UNIX EPOCH*.
+    pub millis: u64,
+}
+
+impl FaketimeConfig {
+    /// Enables faketime in current thread and freezes the time at `millis`, see `enable_millis`.
+    pub fn enable(&self) {
+        crate::enable_millis(self.millis);
+    }
+}
+
+impl Arbitrary for FaketimeConfig {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<FaketimeConfig>;
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        prop_oneof![
+            Just(0),
+            Just(u64::MAX),
+            Just(Y2K_MILLIS),
+            Just(Y2100_MILLIS),
+            0..=Y2100_MILLIS,
+            any::<u64>(),
+        ]
+        .prop_map(|millis| FaketimeConfig { millis })
+        .boxed()
+    }
+}
+
+/// Generates non-decreasing sequences of milliseconds since *UNIX EPOCH*, with the length in
+/// `len`.
+///
+/// ```
+/// proptest::proptest!(|(sequence in faketime::monotonic_millis(1..10))| {
+///     proptest::prop_assert!(sequence.windows(2).all(|pair| pair[0] <= pair[1]));
+/// });
+/// ```
+pub fn monotonic_millis(len: impl Into<SizeRange>) -> impl Strategy<Value = Vec<u64>> {
+    vec(0..=Y2100_MILLIS, len).prop_map(|mut sequence| {
+        sequence.sort_unstable();
+        sequence
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    proptest! {
+        #[test]
+        fn test_advance_from_arbitrary_clock(config: FaketimeConfig, delta in 0..1_000_000u64) {
+            config.enable();
+            let advanced = crate::advance(Duration::from_millis(delta));
+            match config.millis.checked_add(delta) {
+                Some(millis) => prop_assert_eq!(millis, advanced.expect("advance")),
+                None => prop_assert!(advanced.is_err()),
+            }
+        }
+
+        #[test]
+        fn test_monotonic_file(sequence in monotonic_millis(1..20)) {
+            let faketime_file = crate::millis_tempfile(0).expect("create faketime file");
+            crate::enable(&faketime_file);
+            let mut last = 0;
+            for millis in sequence {
+                crate::write_millis(&faketime_file, millis).expect("write millis");
+                let now = crate::unix_time_as_millis();
+                prop_assert!(now >= last);
+                last = now;
+            }
+        }
+    }
+}
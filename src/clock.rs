@@ -0,0 +1,308 @@
+//! Provides the trait `Clock` to inject the time source explicitly, as an alternative to faking
+//! the time per thread.
+//!
+//! The implementations in this module, ordered from the cheapest to the most expensive:
+//!
+//! - `FakeClock` and `ArcClock` load the time from an atomic integer.
+//! - `SystemClock` gets the time from the system.
+//! - `FileClock` reads the time from a timestamp file, which costs syscalls on every call.
+//!
+//! The references, `Box` and `Arc` of clocks are also clocks. See `faketime::rate_limiter` for an
+//! example of a component which reads the time only via an injected clock.
+//!
+//! Clocks can also be registered by name in a `ClockRegistry`, and a thread can switch to a
+//! registered clock via `faketime::enable_named_clock`.
+
+use std::collections::BTreeMap;
+#[cfg(not(disable_faketime))]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// A source of the elapsed time since *UNIX EPOCH*.
+pub trait Clock {
+    /// Gets elapsed time since *UNIX EPOCH*.
+    fn unix_time(&self) -> Duration;
+
+    /// Gets the time as `SystemTime`.
+    ///
+    /// ```
+    /// use faketime::clock::{Clock, FakeClock};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let clock = FakeClock::new(1_000);
+    /// assert_eq!(
+    ///     SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+    ///     clock.now_system_time()
+    /// );
+    /// ```
+    ///
+    /// It is computed from `unix_time`, which never calls `SystemTime::now`, so it also works on
+    /// wasm where the clock implementations get the system time through the JavaScript `Date`.
+    fn now_system_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + self.unix_time()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn unix_time(&self) -> Duration {
+        (**self).unix_time()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn unix_time(&self) -> Duration {
+        (**self).unix_time()
+    }
+}
+
+/// The clock can be shared, so the owner can control the time of the component using it.
+///
+/// ```
+/// use faketime::clock::{Clock, FakeClock};
+/// use std::sync::Arc;
+///
+/// let clock = Arc::new(FakeClock::new(1_000));
+/// let shared: Arc<dyn Clock> = Arc::clone(&clock) as Arc<dyn Clock>;
+/// clock.set_millis(2_000);
+/// assert_eq!(2, shared.unix_time().as_secs());
+/// ```
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn unix_time(&self) -> Duration {
+        (**self).unix_time()
+    }
+}
+
+/// Gets the time from the system, see `faketime::system::unix_time`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn unix_time(&self) -> Duration {
+        crate::system::unix_time()
+    }
+}
+
+/// Returns the time in milliseconds configured by the owner.
+///
+/// ```
+/// use faketime::clock::{Clock, FakeClock};
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new(1_000);
+/// clock.advance(Duration::from_millis(500));
+/// assert_eq!(Duration::from_millis(1_500), clock.unix_time());
+/// ```
+#[derive(Debug, Default)]
+pub struct FakeClock(AtomicU64);
+
+impl FakeClock {
+    /// Creates a clock which returns the specified milliseconds since *UNIX EPOCH*.
+    pub fn new(millis: u64) -> Self {
+        FakeClock(AtomicU64::new(millis))
+    }
+
+    /// Sets the time as milliseconds since *UNIX EPOCH*.
+    pub fn set_millis(&self, millis: u64) {
+        self.0.store(millis, Ordering::SeqCst);
+    }
+
+    /// Advances the time by `delta`, which is truncated to milliseconds. The time saturates at
+    /// `u64::MAX` milliseconds.
+    pub fn advance(&self, delta: Duration) {
+        let delta = crate::convert::duration_to_millis_checked(delta).unwrap_or(u64::MAX);
+        let _ = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |millis| {
+                Some(millis.saturating_add(delta))
+            });
+    }
+}
+
+impl Clock for FakeClock {
+    fn unix_time(&self) -> Duration {
+        Duration::from_millis(self.0.load(Ordering::SeqCst))
+    }
+}
+
+/// Returns the milliseconds stored in a shared atomic integer.
+///
+/// All the clones of the `Arc` observe the same time, so the time can be controlled outside of
+/// the component which owns the clock.
+///
+/// ```
+/// use faketime::clock::{ArcClock, Clock};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// let millis = Arc::new(AtomicU64::new(1_000));
+/// let clock = ArcClock(Arc::clone(&millis));
+/// millis.store(2_000, Ordering::SeqCst);
+/// assert_eq!(2, clock.unix_time().as_secs());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ArcClock(pub Arc<AtomicU64>);
+
+impl Clock for ArcClock {
+    fn unix_time(&self) -> Duration {
+        Duration::from_millis(self.0.load(Ordering::SeqCst))
+    }
+}
+
+/// Reads the time from a timestamp file, and falls back to the system time when an error
+/// occurs.
+///
+/// The file has the same format as the one used by `faketime::enable`, but the clock does not
+/// depend on the faketime settings of the current thread.
+#[cfg(not(disable_faketime))]
+#[derive(Debug, Clone)]
+pub struct FileClock(pub PathBuf);
+
+#[cfg(not(disable_faketime))]
+impl Clock for FileClock {
+    fn unix_time(&self) -> Duration {
+        crate::faketime::read_or_system(&self.0)
+    }
+}
+
+/// The clock shared by name in a `ClockRegistry`.
+pub type SharedClock = Arc<dyn Clock + Send + Sync>;
+
+/// Maps names to clocks, for simulations with many named logical clocks.
+///
+/// The registry is thread-safe. It can be shared by reference between threads, and a clock can
+/// be registered, replaced or read from any thread. The process wide registry returned by
+/// `ClockRegistry::global` is the one used by `faketime::enable_named_clock`.
+///
+/// ```
+/// use faketime::clock::{Clock, ClockRegistry, FakeClock};
+/// use std::sync::Arc;
+///
+/// let registry = ClockRegistry::new();
+/// registry.register("node-1", Arc::new(FakeClock::new(1_000)));
+/// let clock = registry.get("node-1").expect("registered clock");
+/// assert_eq!(1, clock.unix_time().as_secs());
+/// assert!(registry.get("node-2").is_none());
+/// ```
+#[derive(Default)]
+pub struct ClockRegistry {
+    clocks: RwLock<BTreeMap<String, SharedClock>>,
+}
+
+static GLOBAL_REGISTRY: ClockRegistry = ClockRegistry::new();
+
+impl ClockRegistry {
+    /// Creates an empty registry.
+    pub const fn new() -> Self {
+        ClockRegistry {
+            clocks: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Gets the process wide registry.
+    pub fn global() -> &'static ClockRegistry {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Registers the clock with the name, and returns the clock previously registered with the
+    /// name.
+    pub fn register(&self, name: impl Into<String>, clock: SharedClock) -> Option<SharedClock> {
+        self.clocks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.into(), clock)
+    }
+
+    /// Gets the clock registered with the name.
+    pub fn get(&self, name: &str) -> Option<SharedClock> {
+        self.clocks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(clock: &dyn Clock) -> Duration {
+        clock.unix_time()
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let now = crate::system::unix_time();
+        assert!((read(&SystemClock) - now).as_secs() < 60);
+    }
+
+    #[test]
+    fn test_fake_clock() {
+        let clock = FakeClock::new(1_000);
+        assert_eq!(Duration::from_millis(1_000), read(&clock));
+        clock.set_millis(2_000);
+        assert_eq!(Duration::from_millis(2_000), read(&clock));
+        clock.advance(Duration::from_micros(1_500));
+        assert_eq!(Duration::from_millis(2_001), read(&clock));
+    }
+
+    #[test]
+    fn test_fake_clock_advance_saturates() {
+        let clock = FakeClock::new(1_000);
+        clock.advance(Duration::MAX);
+        assert_eq!(Duration::from_millis(u64::MAX), read(&clock));
+        clock.set_millis(u64::MAX - 1);
+        clock.advance(Duration::from_millis(2));
+        assert_eq!(Duration::from_millis(u64::MAX), read(&clock));
+    }
+
+    #[test]
+    fn test_now_system_time() {
+        let clock: Box<dyn Clock> = Box::new(FakeClock::new(123_456));
+        let now = clock.now_system_time();
+        assert_eq!(
+            Duration::from_millis(123_456),
+            now.duration_since(SystemTime::UNIX_EPOCH)
+                .expect("after UNIX EPOCH")
+        );
+    }
+
+    #[test]
+    fn test_arc_clock() {
+        let millis = Arc::new(AtomicU64::new(1_000));
+        let clock = ArcClock(Arc::clone(&millis));
+        assert_eq!(Duration::from_millis(1_000), read(&clock));
+        millis.store(3_000, Ordering::SeqCst);
+        assert_eq!(Duration::from_millis(3_000), read(&clock.clone()));
+    }
+
+    #[test]
+    fn test_clock_registry() {
+        let registry = ClockRegistry::default();
+        assert!(registry.get("a").is_none());
+        assert!(registry
+            .register("a", Arc::new(FakeClock::new(1_000)))
+            .is_none());
+        let previous = registry.register("a", Arc::new(FakeClock::new(2_000)));
+        assert_eq!(
+            Some(Duration::from_secs(1)),
+            previous.map(|c| c.unix_time())
+        );
+        assert_eq!(
+            Some(Duration::from_secs(2)),
+            registry.get("a").map(|c| c.unix_time())
+        );
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_file_clock() {
+        let faketime_file = crate::millis_tempfile(123_456).expect("create faketime file");
+        let clock = FileClock(faketime_file.to_path_buf());
+        assert_eq!(Duration::from_millis(123_456), read(&clock));
+        crate::write_millis(&faketime_file, 654_321).expect("write millis");
+        assert_eq!(Duration::from_millis(654_321), read(&clock));
+    }
+}
@@ -0,0 +1,77 @@
+//! Conversions between `Duration` and milliseconds in `u64`, which is how the timestamp files
+//! store the time.
+
+use std::time::Duration;
+
+/// Converts the duration to milliseconds, truncating the sub-millisecond part.
+///
+/// ```
+/// use faketime::convert::duration_to_millis_checked;
+/// use std::time::Duration;
+///
+/// assert_eq!(Some(1_500), duration_to_millis_checked(Duration::from_micros(1_500_999)));
+/// assert_eq!(None, duration_to_millis_checked(Duration::MAX));
+/// ```
+///
+/// Returns `None` if `u64` is not enough to store the milliseconds.
+pub fn duration_to_millis_checked(duration: Duration) -> Option<u64> {
+    let millis = duration.as_millis();
+    if millis > u128::from(u64::MAX) {
+        None
+    } else {
+        Some(millis as u64)
+    }
+}
+
+/// Converts the milliseconds to duration, which never overflows.
+///
+/// ```
+/// use faketime::convert::millis_to_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(Duration::from_secs(1), millis_to_duration(1_000));
+/// ```
+pub fn millis_to_duration(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_to_millis_checked() {
+        let max_secs = u64::MAX / 1_000;
+        let max_subsec_nanos = (u64::MAX % 1_000) as u32 * 1_000_000 + 999_999;
+        let cases = [
+            (Duration::from_secs(0), Some(0)),
+            (Duration::from_nanos(999_999), Some(0)),
+            (Duration::from_nanos(1_000_000), Some(1)),
+            (Duration::new(1, 999_999_999), Some(1_999)),
+            (Duration::new(max_secs, max_subsec_nanos), Some(u64::MAX)),
+            (Duration::new(max_secs, max_subsec_nanos + 1), None),
+            (Duration::from_secs(max_secs + 1), None),
+            (Duration::MAX, None),
+        ];
+        for (duration, expected) in cases.iter() {
+            assert_eq!(
+                *expected,
+                duration_to_millis_checked(*duration),
+                "{:?}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn test_millis_to_duration() {
+        for millis in [0, 1, 999, 1_000, u64::MAX - 1, u64::MAX].iter() {
+            let duration = millis_to_duration(*millis);
+            assert_eq!(Some(*millis), duration_to_millis_checked(duration));
+        }
+        assert_eq!(
+            Duration::new(u64::MAX / 1_000, 615_000_000),
+            millis_to_duration(u64::MAX)
+        );
+    }
+}
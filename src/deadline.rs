@@ -0,0 +1,93 @@
+//! Provides `Deadline`, which tells whether a timeout has expired in the faked time.
+
+use std::time::Duration;
+
+/// A deadline in the faked time read via `unix_time`.
+///
+/// The deadline expires exactly when the time reaches it: `is_expired` is true and `remaining`
+/// is `None` from that moment on, and the two are always consistent.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use faketime::Deadline;
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_000);
+/// let deadline = Deadline::after(Duration::from_secs(2));
+/// assert_eq!(Some(Duration::from_secs(2)), deadline.remaining());
+/// faketime::advance(Duration::from_secs(2)).expect("advance");
+/// assert!(deadline.is_expired());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline {
+    at: Duration,
+}
+
+impl Deadline {
+    /// Creates a deadline after `timeout` from now. It saturates at the maximum time.
+    pub fn after(timeout: Duration) -> Deadline {
+        Deadline {
+            at: crate::unix_time().saturating_add(timeout),
+        }
+    }
+
+    /// Creates a deadline at the time since *UNIX EPOCH*.
+    pub fn at(time: Duration) -> Deadline {
+        Deadline { at: time }
+    }
+
+    /// Gets the deadline as the time since *UNIX EPOCH*.
+    pub fn time(&self) -> Duration {
+        self.at
+    }
+
+    /// Returns whether the time has reached the deadline.
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_none()
+    }
+
+    /// Gets the time left before the deadline, or `None` if the time has reached the deadline.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.at
+            .checked_sub(crate::unix_time())
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(all(test, not(disable_faketime)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_boundary() {
+        crate::enable_millis(10_000);
+        let deadline = Deadline::after(Duration::from_millis(500));
+        assert_eq!(Duration::from_millis(10_500), deadline.time());
+
+        crate::set_millis(10_499).expect("set millis");
+        assert!(!deadline.is_expired());
+        assert_eq!(Some(Duration::from_millis(1)), deadline.remaining());
+
+        crate::set_millis(10_500).expect("set millis");
+        assert!(deadline.is_expired());
+        assert_eq!(None, deadline.remaining());
+
+        crate::set_millis(10_501).expect("set millis");
+        assert!(deadline.is_expired());
+        assert_eq!(None, deadline.remaining());
+
+        crate::set_millis(0).expect("set millis");
+        assert!(!deadline.is_expired());
+    }
+
+    #[test]
+    fn test_deadline_saturates() {
+        crate::enable_millis(10_000);
+        let deadline = Deadline::after(Duration::MAX);
+        assert_eq!(Deadline::at(Duration::MAX), deadline);
+        assert!(!deadline.is_expired());
+        assert!(Deadline::at(Duration::ZERO).is_expired());
+    }
+}
@@ -12,13 +12,21 @@
 //! RUSTFLAGS="--cfg disable_faketime" cargo build
 //! ```
 //!
-//! By default, without setting the cfg, faketime is available for all the threads.
+//! By default, without setting the cfg, faketime is available for all the threads. Place
+//! `faketime::assert_faketime_disabled!()` in the release builds to enforce the cfg.
 //!
 //! ## Usage
 //!
 //! The faketime setting is per thread. If it is enabled in a thread, a file path is also
-//! configured. The file should store the milliseconds since UNIX EPOCH. This function will first
-//! try to read the time from this file, and fallback to the system time when an error occurs.
+//! configured. The file should store the milliseconds since UNIX EPOCH, or a date in the format
+//! `YYYY-MM-DD`, which means the midnight of that day in UTC, or a RFC 3339 timestamp such as
+//! `2020-01-01T12:00:00Z`. The milliseconds can also be written as a floating point number such
+//! as `1.5779e12`, which is rounded to the nearest integer. Prefix the number with `s:`, such as
+//! `s:1577836800`, to store seconds instead of milliseconds, which is what many tools print, or
+//! with `ns:` to store nanoseconds, which are truncated to milliseconds. An optional second line
+//! stores the CRC32 of the first line in hex, see `faketime::write_millis_checksummed`. This
+//! function will first try to read the time from this file, and fallback to the system time when
+//! an error occurs.
 //!
 //! The most straightforward way to enable faketime is calling `faketime::enable(path)` in the
 //! thread, and `path` is the configured timestamp file. It also overrides the auto-detected
@@ -31,12 +39,28 @@
 //! assert_eq!(faketime::unix_time().as_secs(), 100);
 //! ```
 //!
+//! To fake time only for a block of code, `faketime::with_frozen(millis, f)` freezes the time in
+//! memory while running the closure, and restores the previous settings afterwards.
+//!
+//! ```
+//! let secs = faketime::with_frozen(100_000, || faketime::unix_time().as_secs());
+//! assert_eq!(secs, 100);
+//! assert_ne!(faketime::unix_time().as_secs(), 100);
+//! ```
+//!
 //! In each thread, when this function is first invoked, and neither `faketime::enable(path)` nor
 //! `faketime::disable()` has been invoked in the thread already, it will detect whether faketime
 //! should be enabled and which timestamp file should be used.
 //!
-//! First, if the environment variable `FAKETIME` exists, faketime is enabled, and the timestamp
-//! file path is the environment variable value.
+//! If the thread has a name which starts with `REALTIME` literally, faketime is disabled in the
+//! thread. It takes precedence over all the other rules below, so a process can mix the threads
+//! using the faked time and the real clock declaratively via the thread names.
+//!
+//! Otherwise, if a process wide timestamp file has been set via `faketime::set_global_path(path)`,
+//! faketime is enabled and the file is used.
+//!
+//! Otherwise, if the environment variable `FAKETIME` exists, faketime is enabled, and the
+//! timestamp file path is the environment variable value.
 //!
 //! ```
 //! use std::env;
@@ -50,10 +74,25 @@
 //!     .expect("join thread");
 //! ```
 //!
-//! If the environment variable `FAKETIME` is missing, but this thread has a name and the name
+//! More environment variable names can be registered via `faketime::add_env_key(key)`. They are
+//! checked after `FAKETIME`, in the order of registration, and the first existing one wins.
+//!
+//! If the environment variable `FAKETIME` is missing, but `FAKETIME_MILLIS` exists and is a valid
+//! time in the timestamp file format, faketime is enabled and the time is frozen in memory at the
+//! environment variable value. See `faketime::command_env`, which sets it for child processes.
+//!
+//! If all the environment variables are missing, but this thread has a name and the name
 //! starts with `FAKETIME=` literally, faketime is also enabled, and the timestamp file is the
 //! portion of the thread name after `FAKETIME=`.
 //!
+//! The environment variable values are used as the paths without the conversion to strings, so
+//! the non-UTF-8 paths on unix are kept intact. The thread names are always UTF-8 in Rust, use the
+//! environment variables or `faketime::enable(path)` for the non-UTF-8 paths.
+//!
+//! When the environment variable `FAKETIME_MONOTONIC` is `1`, the auto-detected timestamp file is
+//! followed like `faketime::enable_monotonic(path)`, so the time never goes backward. The files
+//! enabled explicitly via `faketime::enable(path)` are not affected.
+//!
 //! ```
 //! use std::thread;
 //!
@@ -79,6 +118,20 @@
 //!     .expect("join thread");
 //! ```
 //!
+//! ## Tracing
+//!
+//! With the cargo feature `tracing`, the changes of the faketime settings and the time set via
+//! functions such as `set_millis` and `advance` are emitted as `tracing` events in the target
+//! `faketime` at the debug level, with the thread id.
+//!
+//! ## Write Format
+//!
+//! The timestamp files written by this crate, such as via `faketime::write_millis`, store the
+//! milliseconds by default. The format can be changed process wide via
+//! `faketime::set_write_format`, or via the environment variable `FAKETIME_FMT`, which is one of
+//! `millis`, `seconds`, `rfc3339` and `nanos`, so the writers and the readers agree on it. All the
+//! formats are accepted by the reader.
+//!
 //! ## Atomic Write
 //!
 //! This function reads timestamp from the file when faketime is enabled. To ensure the written
@@ -93,29 +146,419 @@
 //! mv /tmp/faketime_ /tmp/faketime
 //! ```
 
-use crate::system::unix_time as system_unix_time;
+use crate::clock::{Clock, ClockRegistry};
+use crate::convert::{duration_to_millis_checked, millis_to_duration};
+use crate::global_clock;
+use crate::random::splitmix64;
+use crate::system::{monotonic as system_monotonic, unix_time as system_unix_time};
 use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
 use std::env;
-use std::fs;
-use std::io::{Error, ErrorKind, Write};
-use std::ops::Deref;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::panic::{self, PanicHookInfo};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tempfile::{NamedTempFile, TempPath};
 
 pub use std::io::Result;
 
 thread_local! {
-    /// Some(true): Enabled
-    /// Some(false): Disabled
-    /// None: Undecided
-    static FAKETIME_ENABLED: Cell<Option<bool>> = Cell::new(None);
-    static FAKETIME_PATH: RefCell<PathBuf> = Default::default();
+    static FAKETIME_MODE: RefCell<Mode> = const { RefCell::new(Mode::Undecided) };
+    /// The value of `GENERATION` when `FAKETIME_MODE` was set.
+    static MODE_GENERATION: Cell<u64> = const { Cell::new(0) };
+    /// The timestamp file for `monotonic`.
+    static MONOTONIC_PATH: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    /// The time returned by the last `poll_changes` call.
+    static LAST_POLLED: Cell<Option<Duration>> = const { Cell::new(None) };
+    /// The faked time when `FAKETIME_MODE` was set, or `None` if faketime is not enabled.
+    static ENABLED_AT: Cell<Option<Duration>> = const { Cell::new(None) };
+    /// The system monotonic time when faketime was enabled, or `None` if faketime is not enabled.
+    static ENABLED_MONOTONIC: Cell<Option<Duration>> = const { Cell::new(None) };
+    /// Whether `enable_once` has taken effect.
+    static ENABLED_ONCE: Cell<bool> = const { Cell::new(false) };
+    /// Counts how many times `FAKETIME_MODE` has been set, see `mode_epoch`.
+    static MODE_EPOCH: Cell<u64> = const { Cell::new(0) };
+    /// Counts the calls of `unix_time`, see `call_count`.
+    static CALL_COUNT: Cell<u64> = const { Cell::new(0) };
 }
 
+/// Increased by `reset_all` to ask all threads to auto-detect the faketime settings again.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the bounds have been set, which avoids taking the lock when it is false.
+static HAS_BOUNDS: AtomicBool = AtomicBool::new(false);
+static BOUNDS: RwLock<Option<(Duration, Duration)>> = RwLock::new(None);
+
+/// Whether the timestamp files are read back after being written, see `set_verify_writes`.
+static VERIFY_WRITES: AtomicBool = AtomicBool::new(false);
+
+/// The format set via `set_write_format`.
+static WRITE_FORMAT: RwLock<Option<WriteFormat>> = RwLock::new(None);
+
+/// The timestamp file set via `set_global_path`.
+static GLOBAL_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Environment variable names registered via `add_env_key`, besides `FAKETIME`.
+static ENV_KEYS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Send + Sync + 'static>;
+
+/// The panic hook replaced by `install_panic_dump`.
+static PREVIOUS_PANIC_HOOK: Mutex<Option<Arc<PanicHook>>> = Mutex::new(None);
+
 const KEY_FAKETIME: &str = "FAKETIME";
+const KEY_FAKETIME_MILLIS: &str = "FAKETIME_MILLIS";
+const KEY_FAKETIME_MONOTONIC: &str = "FAKETIME_MONOTONIC";
 const PREFIX_FAKETIME_EQ: &str = "FAKETIME=";
+const PREFIX_REALTIME: &str = "REALTIME";
+const PREFIX_SECONDS: &str = "s:";
+const PREFIX_NANOS: &str = "ns:";
+const KEY_FAKETIME_FMT: &str = "FAKETIME_FMT";
+
+enum Mode {
+    /// Neither enabled nor disabled, and will be auto-detected on first use.
+    Undecided,
+    Disabled,
+    /// Reads the timestamp file from the path.
+    Path(PathBuf),
+    /// Reads the timestamp file from the path, but never goes back before the floor, which is the
+    /// latest time returned so far.
+    Monotonic {
+        path: PathBuf,
+        floor: Duration,
+    },
+    /// Reads the timestamp file from the path, but falls back to the default time instead of the
+    /// system time.
+    Default {
+        path: PathBuf,
+        default: Duration,
+    },
+    /// Reads the timestamp file, and rounds the time down to the multiple of the quantum.
+    Quantized {
+        path: PathBuf,
+        quantum: Duration,
+    },
+    /// Reads the last value of the append-only timestamp file.
+    Tail(PathBuf),
+    /// Reads the timestamp file from the opened handle.
+    File(FileHandle),
+    /// Returns the time in memory.
+    Frozen(Duration),
+    /// Shifts the system time by the milliseconds.
+    Offset(i64),
+    Replay(Replay),
+    Jitter(Jitter),
+    Scaled(Scaled),
+    /// Calls the closure set via `set_clock_closure`.
+    Closure(ClockClosure),
+    /// Reads the clock registered with the name in `ClockRegistry::global`, and falls back to the
+    /// system time if no clock is registered with the name.
+    Named(String),
+    /// Holds the time paused via `pause`, and resumes it with the scale factor, or frozen if the
+    /// factor is `None`.
+    Paused {
+        time: Duration,
+        factor: Option<f64>,
+    },
+}
+
+type ClockClosure = Box<dyn Fn() -> Duration + Send>;
+
+struct FileHandle {
+    file: File,
+    /// Whether the file was linked in the file system when it was enabled, it's used to detect
+    /// whether the file has been replaced or removed.
+    linked: bool,
+    /// Whether the file is enabled via `enable_readonly`.
+    readonly: bool,
+}
+
+impl Mode {
+    fn unix_time(&mut self) -> Duration {
+        match self {
+            Mode::Path(path) => read_or_system(path),
+            Mode::Monotonic { path, floor } => {
+                *floor = read_or_system(path).max(*floor);
+                *floor
+            }
+            Mode::Default { path, default } => read_or_default(path, *default),
+            Mode::Quantized { path, quantum } => quantize(read_or_system(path), *quantum),
+            Mode::Tail(path) => or_system(record_read(|| read_tail_millis(path))),
+            Mode::File(handle) => or_system(record_read(|| handle.read_millis())),
+            Mode::Frozen(time) => *time,
+            Mode::Offset(offset) => offset_system_time(*offset),
+            Mode::Replay(replay) => replay.next(),
+            Mode::Jitter(jitter) => jitter.next(),
+            Mode::Scaled(scaled) => scaled.now(),
+            Mode::Closure(closure) => closure(),
+            Mode::Named(name) => ClockRegistry::global()
+                .get(name)
+                .map_or_else(system_unix_time, |clock| clock.unix_time()),
+            Mode::Paused { time, .. } => *time,
+            Mode::Undecided | Mode::Disabled => system_unix_time(),
+        }
+    }
+
+    /// Gets the faked time like `unix_time`, but without consuming the replay log or the jitter
+    /// sequence. Returns `None` if faketime is not enabled, or the time cannot be read without
+    /// calling the closure set via `set_clock_closure` or the clock of `enable_named_clock`.
+    fn peek(&mut self) -> Option<Duration> {
+        match self {
+            Mode::Undecided | Mode::Disabled | Mode::Closure(_) | Mode::Named(_) => None,
+            Mode::Replay(replay) => Some(millis_to_duration(replay.values[replay.position()])),
+            Mode::Jitter(jitter) => Some(millis_to_duration(jitter.base_millis)),
+            _ => Some(self.unix_time()),
+        }
+    }
+
+    /// Gets the path of the timestamp file which can be updated.
+    fn path(&self) -> Result<&Path> {
+        match self {
+            Mode::Path(path)
+            | Mode::Monotonic { path, .. }
+            | Mode::Default { path, .. }
+            | Mode::Quantized { path, .. }
+            | Mode::Tail(path) => Ok(path),
+            Mode::File(handle) if handle.readonly => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "faketime is enabled read-only",
+            )),
+            Mode::File(_) => Err(Error::other("faketime is enabled via a file handle")),
+            Mode::Jitter(_) => Err(Error::other("faketime is enabled with jitter")),
+            Mode::Frozen(_) => Err(Error::other("faketime is frozen in memory")),
+            Mode::Offset(_) => Err(Error::other("faketime is enabled with an offset")),
+            Mode::Replay(_) => Err(Error::other("faketime is replaying a log")),
+            Mode::Scaled(_) => Err(Error::other("faketime is scaled")),
+            Mode::Closure(_) => Err(Error::other("faketime is enabled with a closure")),
+            Mode::Named(_) => Err(Error::other("faketime is enabled with a named clock")),
+            Mode::Paused { .. } => Err(Error::other("faketime is paused")),
+            Mode::Undecided | Mode::Disabled => Err(Error::other("faketime is not enabled")),
+        }
+    }
+
+    /// Gets the faked time in milliseconds without falling back to the system time.
+    fn millis(&self) -> Result<u64> {
+        match self {
+            Mode::Frozen(time) | Mode::Paused { time, .. } => Ok(Millis::from(*time).0),
+            Mode::Scaled(scaled) => Ok(Millis::from(scaled.now()).0),
+            Mode::Tail(path) => read_tail_millis(path),
+            _ => read_millis(self.path()?),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Mode::Undecided => "undecided".to_string(),
+            Mode::Disabled => "disabled".to_string(),
+            Mode::Path(path) => format!("enabled with file {}", path.display()),
+            Mode::Monotonic { path, .. } => {
+                format!("enabled monotonic with file {}", path.display())
+            }
+            Mode::Default { path, default } => format!(
+                "enabled with file {} defaulting to {}ms",
+                path.display(),
+                Millis::from(*default).0
+            ),
+            Mode::Quantized { path, quantum } => format!(
+                "enabled with file {} quantized to {:?}",
+                path.display(),
+                quantum
+            ),
+            Mode::Tail(path) => format!("enabled with the last value of file {}", path.display()),
+            Mode::File(handle) if handle.readonly => "enabled read-only".to_string(),
+            Mode::File(_) => "enabled with a file handle".to_string(),
+            Mode::Frozen(time) => format!("frozen at {}ms", Millis::from(*time).0),
+            Mode::Offset(offset) => format!("enabled with an offset of {}ms", offset),
+            Mode::Replay(replay) => format!(
+                "replaying {} of {} values",
+                replay.next,
+                replay.values.len()
+            ),
+            Mode::Jitter(jitter) => format!(
+                "enabled with jitter of {}ms around {}ms",
+                jitter.max_jitter_millis, jitter.base_millis
+            ),
+            Mode::Scaled(scaled) => {
+                format!("scaled by {} from {}ms", scaled.factor, scaled.base_millis)
+            }
+            Mode::Closure(_) => "enabled with a closure".to_string(),
+            Mode::Named(name) => format!("enabled with clock {}", name),
+            Mode::Paused { time, .. } => format!("paused at {}ms", Millis::from(*time).0),
+        }
+    }
+
+    /// Sets the faked time and returns the previous one, which falls back to the system time, or
+    /// the default time of `enable_with_default`, if the timestamp file cannot be read. The time
+    /// is truncated to milliseconds when it is written to the timestamp file.
+    fn set_time(&mut self, time: Duration) -> Result<Duration> {
+        let previous = match self {
+            Mode::Frozen(frozen) | Mode::Paused { time: frozen, .. } => mem::replace(frozen, time),
+            Mode::Scaled(scaled) => scaled.rebase(time),
+            Mode::Default { path, default } => {
+                let previous = read_or_default(&*path, *default);
+                write_millis(path, Millis::from(time).0)?;
+                previous
+            }
+            _ => {
+                let path = self.path()?;
+                let previous = read_or_system(path);
+                write_millis(path, Millis::from(time).0)?;
+                previous
+            }
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            target: "faketime",
+            thread = ?thread::current().id(),
+            time = ?time,
+            previous = ?previous,
+            "faketime time set"
+        );
+        Ok(previous)
+    }
+}
+
+fn offset_system_time(offset: i64) -> Duration {
+    let delta = Duration::from_millis(offset.unsigned_abs());
+    if offset >= 0 {
+        system_unix_time().saturating_add(delta)
+    } else {
+        system_unix_time().saturating_sub(delta)
+    }
+}
+
+/// What `unix_time` returns after all the values in the replay log have been consumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReplayEnd {
+    /// Keeps returning the last value.
+    Hold,
+    /// Starts over from the first value.
+    Loop,
+}
+
+struct Replay {
+    values: Vec<u64>,
+    next: usize,
+    end: ReplayEnd,
+}
+
+impl Replay {
+    /// Gets the index of the value which is returned next.
+    fn position(&self) -> usize {
+        match self.end {
+            ReplayEnd::Hold => self.next.min(self.values.len() - 1),
+            ReplayEnd::Loop => self.next % self.values.len(),
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        if self.next == self.values.len() {
+            match self.end {
+                ReplayEnd::Hold => self.next -= 1,
+                ReplayEnd::Loop => self.next = 0,
+            }
+        }
+        let millis = self.values[self.next];
+        self.next += 1;
+        Duration::from_millis(millis)
+    }
+}
+
+struct Jitter {
+    base_millis: u64,
+    max_jitter_millis: u64,
+    state: u64,
+}
+
+impl Jitter {
+    fn next(&mut self) -> Duration {
+        let random = splitmix64(&mut self.state);
+        let jitter = match self.max_jitter_millis.checked_add(1) {
+            Some(range) => random % range,
+            None => random,
+        };
+        Duration::from_millis(self.base_millis.saturating_add(jitter))
+    }
+}
+
+struct Scaled {
+    base_millis: u64,
+    factor: f64,
+    /// The real monotonic time when the scaled time was at `base_millis`.
+    anchor: Duration,
+}
+
+impl Scaled {
+    fn new(base_millis: u64, factor: f64) -> Self {
+        assert!(
+            factor >= 0.0 && factor.is_finite(),
+            "faketime scale factor must be finite and non-negative"
+        );
+        Scaled {
+            base_millis,
+            factor,
+            anchor: system_monotonic(),
+        }
+    }
+
+    /// Jumps to `time` immediately and returns the time before the jump. The time keeps being
+    /// scaled from there.
+    fn rebase(&mut self, time: Duration) -> Duration {
+        let previous = self.now();
+        self.base_millis = Millis::from(time).0;
+        self.anchor = system_monotonic();
+        previous
+    }
+
+    fn now(&self) -> Duration {
+        let elapsed = system_monotonic().saturating_sub(self.anchor);
+        // The float to integer cast saturates.
+        let scaled = (elapsed.as_secs_f64() * 1_000.0 * self.factor) as u64;
+        Duration::from_millis(self.base_millis.saturating_add(scaled))
+    }
+}
+
+impl FileHandle {
+    fn new(file: File, readonly: bool) -> Self {
+        let linked = is_linked(&file).unwrap_or(false);
+        FileHandle {
+            file,
+            linked,
+            readonly,
+        }
+    }
+
+    fn read_millis(&mut self) -> Result<u64> {
+        if self.linked && !is_linked(&self.file)? {
+            return Err(Error::other("timestamp file has been replaced or removed"));
+        }
+        let mut text = String::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_string(&mut text)?;
+        parse_contents(&text)
+    }
+}
+
+#[cfg(unix)]
+fn is_linked(file: &File) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    file.metadata().map(|metadata| metadata.nlink() > 0)
+}
+
+/// The link count is not available, so the replacement of the file is not detected, see
+/// `enable_file`.
+#[cfg(not(unix))]
+fn is_linked(_file: &File) -> Result<bool> {
+    Ok(false)
+}
 
 /// Gets elapsed time since *UNIX EPOCH*.
 ///
@@ -123,109 +566,4555 @@ const PREFIX_FAKETIME_EQ: &str = "FAKETIME=";
 ///
 /// Panics if the time is before *UNIX EPOCH*.
 pub fn unix_time() -> Duration {
-    FAKETIME_ENABLED.with(|enabled_cell| match enabled_cell.get() {
-        Some(true) => FAKETIME_PATH.with(|path_cell| read_or_system(path_cell.borrow().deref())),
-        Some(false) => system_unix_time(),
-        None => auto_detect(&enabled_cell),
-    })
+    CALL_COUNT.with(|count| count.set(count.get().wrapping_add(1)));
+    let now = global_unix_time().unwrap_or_else(thread_unix_time);
+    if HAS_BOUNDS.load(Ordering::Acquire) {
+        clamp(now)
+    } else {
+        now
+    }
 }
 
-fn auto_detect(enabled_cell: &Cell<Option<bool>>) -> Duration {
-    if let Some(path) = match env::var(KEY_FAKETIME) {
-        Ok(val) => Some(PathBuf::from(val)),
-        _ => match thread::current().name() {
-            Some(name) if name.starts_with(PREFIX_FAKETIME_EQ) => {
-                Some(PathBuf::from(&name[PREFIX_FAKETIME_EQ.len()..]))
-            }
-            _ => None,
-        },
-    } {
-        let duration = read_or_system(&path);
-        FAKETIME_PATH.with(|file_cell| file_cell.replace(path));
-        enabled_cell.set(Some(true));
-        duration
+/// Gets the time from the faketime settings of current thread. The clock of `enable_named_clock`
+/// is called after the settings are released, so it can use the other functions of this crate.
+fn thread_unix_time() -> Duration {
+    thread_time(Mode::unix_time)
+}
+
+/// Gets the time like `thread_unix_time`, but peeks it via `Mode::peek` when possible.
+fn thread_peek_time() -> Duration {
+    thread_time(|mode| mode.peek().unwrap_or_else(|| mode.unix_time()))
+}
+
+fn thread_time(read: fn(&mut Mode) -> Duration) -> Duration {
+    let named_clock = with_mode(|mode| match mode {
+        Mode::Named(name) => Err(ClockRegistry::global().get(name)),
+        _ => Ok(read(mode)),
+    });
+    named_clock
+        .unwrap_or_else(|clock| clock.map_or_else(system_unix_time, |clock| clock.unix_time()))
+}
+
+/// Gets how many times `unix_time` has been called in current thread since the thread started or
+/// `reset_call_count` was called.
+///
+/// It is a cheap diagnostic without the cargo feature `metrics`, for example, to catch a hot loop
+/// which reads the time unexpectedly often. The functions built on top of `unix_time`, such as
+/// `unix_time_as_millis`, are counted too. It always returns zero when the rust cfg
+/// `disable_faketime` is set.
+///
+/// ```
+/// faketime::reset_call_count();
+/// for _ in 0..3 {
+///     faketime::unix_time();
+/// }
+/// # #[cfg(not(disable_faketime))]
+/// assert_eq!(3, faketime::call_count());
+/// ```
+pub fn call_count() -> u64 {
+    CALL_COUNT.with(Cell::get)
+}
+
+/// Resets the counter of `call_count` in current thread to zero.
+pub fn reset_call_count() {
+    CALL_COUNT.with(|count| count.set(0));
+}
+
+fn clamp(now: Duration) -> Duration {
+    match *BOUNDS.read().unwrap_or_else(PoisonError::into_inner) {
+        Some((min, max)) => now.clamp(min, max),
+        None => now,
+    }
+}
+
+/// Clamps the time returned by `unix_time` in all threads between `min` and `max` inclusively.
+///
+/// The bounds are applied after all the other faketime settings, including the clock installed
+/// via `install_as_global_default` and the fallback to the system time. They do not apply to
+/// `monotonic`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::set_bounds(Duration::from_secs(100), Duration::from_secs(200));
+/// faketime::enable_millis(0);
+/// assert_eq!(100, faketime::unix_time().as_secs());
+/// faketime::enable_millis(u64::MAX);
+/// assert_eq!(200, faketime::unix_time().as_secs());
+///
+/// faketime::clear_bounds();
+/// assert_eq!(u64::MAX, faketime::unix_time_as_millis());
+/// ```
+///
+/// ## Panics
+///
+/// Panics if `min` is greater than `max`.
+pub fn set_bounds(min: Duration, max: Duration) {
+    assert!(min <= max, "faketime bounds min must not exceed max");
+    *BOUNDS.write().unwrap_or_else(PoisonError::into_inner) = Some((min, max));
+    HAS_BOUNDS.store(true, Ordering::Release);
+}
+
+/// Removes the bounds set via `set_bounds`.
+pub fn clear_bounds() {
+    HAS_BOUNDS.store(false, Ordering::Release);
+    *BOUNDS.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+fn global_unix_time() -> Option<Duration> {
+    global_clock::with(|clock| clock.unix_time())
+}
+
+/// Returns the current time if it has changed since the last call in current thread.
+///
+/// The first call in a thread always returns the current time.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert_eq!(1, faketime::poll_changes().expect("first poll").as_secs());
+/// assert_eq!(None, faketime::poll_changes());
+///
+/// faketime::write_millis(&faketime_file, 2_000).expect("write millis");
+/// assert_eq!(2, faketime::poll_changes().expect("changed").as_secs());
+/// ```
+pub fn poll_changes() -> Option<Duration> {
+    let now = unix_time();
+    let last = LAST_POLLED.with(|cell| cell.replace(Some(now)));
+    if last == Some(now) {
+        None
     } else {
-        enabled_cell.set(Some(false));
-        system_unix_time()
+        Some(now)
     }
 }
 
-/// Enables faketime in current thread and use the specified timestamp file.
-pub fn enable<T: AsRef<Path>>(path: T) {
-    let path_buf = path.as_ref().to_path_buf();
-    FAKETIME_PATH.with(|cell| cell.replace(path_buf));
-    FAKETIME_ENABLED.with(|cell| cell.set(Some(true)));
+/// Gets elapsed time of a monotonic clock since the anchor.
+///
+/// It is faked independently from `unix_time`. By default it returns `faketime::system::monotonic`
+/// even if `unix_time` is faked. When a monotonic timestamp file is configured in current thread
+/// via `enable_monotonic_file`, it reads the milliseconds since the anchor from the file, and
+/// falls back to the system monotonic clock when an error occurs.
+///
+/// ```
+/// let monotonic_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable_monotonic_file(&monotonic_file);
+/// assert_eq!(1, faketime::monotonic().as_secs());
+/// ```
+pub fn monotonic() -> Duration {
+    MONOTONIC_PATH.with(|path_cell| match path_cell.borrow().as_ref() {
+        Some(path) => read_millis(path)
+            .ok()
+            .map_or_else(system_monotonic, Duration::from_millis),
+        None => system_monotonic(),
+    })
 }
 
-/// Disables faketime in current thread.
-pub fn disable() {
-    FAKETIME_ENABLED.with(|cell| cell.set(Some(false)));
+/// Fakes `monotonic` in current thread and reads the elapsed milliseconds since the anchor from
+/// the specified timestamp file.
+///
+/// The file has the same format as the one for `unix_time`. It is the caller's duty to not
+/// decrease the value in the file.
+pub fn enable_monotonic_file<T: AsRef<Path>>(path: T) {
+    MONOTONIC_PATH.with(|path_cell| path_cell.replace(Some(path.as_ref().to_path_buf())));
+}
+
+/// Stops faking `monotonic` in current thread.
+pub fn disable_monotonic_file() {
+    MONOTONIC_PATH.with(|path_cell| path_cell.replace(None));
+}
+
+/// Installs the clock as the time source of `unix_time` in all threads.
+///
+/// The installed clock takes precedence over the faketime settings of every thread, including
+/// the ones set explicitly via `enable` or `disable`. Installing another clock replaces the
+/// previous one.
+///
+/// Reading the installed clock does not take any lock. The clock is dropped after it is replaced
+/// by another clock or uninstalled via `clear_all`, once no thread is reading it. If a thread is
+/// still reading it at that moment, it is dropped by the next call of this function or
+/// `clear_all` instead.
+///
+/// ```
+/// use faketime::clock::FakeClock;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// faketime::install_as_global_default(Arc::new(FakeClock::new(123_456)));
+/// thread::spawn(|| assert_eq!(123, faketime::unix_time().as_secs()))
+///     .join()
+///     .expect("join thread");
+/// ```
+pub fn install_as_global_default(clock: Arc<dyn Clock + Send + Sync>) {
+    global_clock::store(Some(clock));
 }
 
-fn read_millis<T: AsRef<Path>>(path: T) -> Result<u64> {
-    fs::read_to_string(path).and_then(|text| {
-        text.trim()
-            .parse()
-            .map_err(|err| Error::new(ErrorKind::Other, err))
+fn with_mode<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Mode) -> R,
+{
+    FAKETIME_MODE.with(|mode_cell| {
+        let mut mode = mode_cell.borrow_mut();
+        let generation = GENERATION.load(Ordering::Acquire);
+        if MODE_GENERATION.with(|cell| cell.replace(generation)) != generation {
+            *mode = Mode::Undecided;
+        }
+        if let Mode::Undecided = *mode {
+            *mode = detect();
+            mode_changed(&mut mode);
+        }
+        f(&mut mode)
     })
 }
 
-fn read_or_system<T: AsRef<Path>>(path: T) -> Duration {
-    read_millis(path)
+fn set_mode(mode: Mode) {
+    replace_mode(mode);
+}
+
+fn replace_mode(mut mode: Mode) -> Mode {
+    mode_changed(&mut mode);
+    let generation = GENERATION.load(Ordering::Acquire);
+    MODE_GENERATION.with(|cell| cell.set(generation));
+    FAKETIME_MODE.with(|mode_cell| mode_cell.replace(mode))
+}
+
+/// Updates the states which depend on the faketime settings of current thread.
+fn mode_changed(mode: &mut Mode) {
+    register(mode);
+    trace_mode(mode);
+    ENABLED_AT.with(|cell| cell.set(mode.peek()));
+    let enabled = !matches!(mode, Mode::Undecided | Mode::Disabled);
+    ENABLED_MONOTONIC.with(|cell| cell.set(enabled.then(crate::system::monotonic)));
+    MODE_EPOCH.with(|cell| cell.set(cell.get().wrapping_add(1)));
+}
+
+/// Gets a counter which changes whenever the faketime settings of current thread are changed.
+///
+/// Changing the time without changing the settings, such as `set_millis` and `advance`, keeps the
+/// counter.
+pub(crate) fn mode_epoch() -> u64 {
+    MODE_EPOCH.with(Cell::get)
+}
+
+#[cfg(feature = "tracing")]
+fn trace_mode(mode: &Mode) {
+    tracing::debug!(
+        target: "faketime",
+        thread = ?thread::current().id(),
+        mode = %mode.describe(),
+        "faketime settings changed"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+fn trace_mode(_mode: &Mode) {}
+
+#[cfg(feature = "registry")]
+fn register(mode: &Mode) {
+    crate::registry::update(match mode {
+        Mode::Undecided | Mode::Disabled => None,
+        Mode::Path(path)
+        | Mode::Monotonic { path, .. }
+        | Mode::Default { path, .. }
+        | Mode::Quantized { path, .. }
+        | Mode::Tail(path) => Some(Some(path.clone())),
+        _ => Some(None),
+    });
+}
+
+#[cfg(not(feature = "registry"))]
+fn register(_mode: &Mode) {}
+
+/// Restores the previous faketime settings of current thread on drop.
+struct ModeGuard(Option<(Mode, Option<Duration>, Option<Duration>)>);
+
+impl ModeGuard {
+    fn replace(mode: Mode) -> Self {
+        let enabled_at = ENABLED_AT.with(Cell::get);
+        let enabled_monotonic = ENABLED_MONOTONIC.with(Cell::get);
+        ModeGuard(Some((replace_mode(mode), enabled_at, enabled_monotonic)))
+    }
+}
+
+impl Drop for ModeGuard {
+    fn drop(&mut self) {
+        if let Some((mode, enabled_at, enabled_monotonic)) = self.0.take() {
+            set_mode(mode);
+            ENABLED_AT.with(|cell| cell.set(enabled_at));
+            ENABLED_MONOTONIC.with(|cell| cell.set(enabled_monotonic));
+        }
+    }
+}
+
+fn detect() -> Mode {
+    if thread::current()
+        .name()
+        .is_some_and(|name| name.starts_with(PREFIX_REALTIME))
+    {
+        return Mode::Disabled;
+    }
+    if let Some(path) = global_path() {
+        return follow(path);
+    }
+    if let Some(path) = env_path() {
+        return follow(path);
+    }
+    if let Some(millis) = env::var(KEY_FAKETIME_MILLIS)
         .ok()
-        .map_or_else(system_unix_time, Duration::from_millis)
+        .and_then(|val| parse_millis(val.trim()).ok())
+    {
+        return Mode::Frozen(millis_to_duration(millis));
+    }
+    match thread::current().name() {
+        Some(name) if name.starts_with(PREFIX_FAKETIME_EQ) => {
+            follow(PathBuf::from(&name[PREFIX_FAKETIME_EQ.len()..]))
+        }
+        _ => Mode::Disabled,
+    }
 }
 
-/// Writes time as milliseconds since *UNIX EPOCH* into the specified timestamp file.
-pub fn write_millis<T: AsRef<Path>>(path: T, millis: u64) -> Result<()> {
-    let mut file = NamedTempFile::new()?;
-    file.write_all(millis.to_string().as_bytes())?;
-    file.into_temp_path().persist(path)?;
-    Ok(())
+/// Follows the auto-detected timestamp file, and applies the monotonic floor if the environment
+/// variable `FAKETIME_MONOTONIC` is `1`.
+fn follow(path: PathBuf) -> Mode {
+    if env::var_os(KEY_FAKETIME_MONOTONIC).is_some_and(|val| val == "1") {
+        Mode::Monotonic {
+            path,
+            floor: Duration::ZERO,
+        }
+    } else {
+        Mode::Path(path)
+    }
 }
 
-/// Writes time into a temporary file and return the file.
+fn global_path() -> Option<PathBuf> {
+    GLOBAL_PATH
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Gets the path of the timestamp file of current thread.
+pub(crate) fn current_path() -> Result<PathBuf> {
+    with_mode(|mode| mode.path().map(Path::to_path_buf))
+}
+
+/// Sets the timestamp file for all the threads which auto-detect the faketime settings.
 ///
+/// It takes precedence over the environment variables and thread names in auto-detection. The
+/// threads which have already decided the faketime settings are not affected.
 ///
-/// It returns the handle to the timestamp file on success.
+/// ```
+/// use std::thread;
 ///
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// faketime::set_global_path(&faketime_file);
+///
+/// thread::spawn(|| assert_eq!(123, faketime::unix_time().as_secs()))
+///     .join()
+///     .expect("join thread");
 /// ```
-/// use std::fs;
+pub fn set_global_path<T: AsRef<Path>>(path: T) {
+    let mut global_path = GLOBAL_PATH.write().unwrap_or_else(PoisonError::into_inner);
+    *global_path = Some(path.as_ref().to_path_buf());
+}
+
+/// Writes time as milliseconds since *UNIX EPOCH* into the timestamp file set via
+/// `set_global_path`.
+///
+/// All the threads reading the file observe the new time on their next read.
+///
+/// Returns an error if the global timestamp file is not set.
+pub fn set_millis_all_threads(millis: u64) -> Result<()> {
+    let path = global_path().ok_or_else(|| Error::other("global timestamp file is not set"))?;
+    write_millis(path, millis)
+}
+
+/// Reads the path via `var_os`, so the non-UTF-8 paths are kept as they are.
+fn env_path() -> Option<PathBuf> {
+    if let Some(val) = env::var_os(KEY_FAKETIME) {
+        return Some(PathBuf::from(val));
+    }
+    let env_keys = ENV_KEYS.read().unwrap_or_else(PoisonError::into_inner);
+    env_keys.iter().find_map(env::var_os).map(PathBuf::from)
+}
+
+/// Registers another environment variable name to auto-detect the timestamp file.
+///
+/// The environment variable `FAKETIME` is always checked first, then the registered names in
+/// the order of registration. The first existing one is used as the timestamp file path.
 ///
-/// let faketime_file = faketime::millis_tempfile(123).expect("create faketime file");
-/// assert_eq!(fs::read_to_string(&faketime_file).ok(), Some("123".to_string()));
 /// ```
+/// use std::env;
+/// use std::thread;
 ///
-/// The file is deleted when the handle goes out of scope.
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// faketime::add_env_key("faketime");
+/// env::set_var("faketime", faketime_file.as_os_str());
 ///
+/// thread::spawn(|| assert_eq!(123, faketime::unix_time().as_secs()))
+///     .join()
+///     .expect("join thread");
 /// ```
-/// let path = {
-///     let faketime_file = faketime::millis_tempfile(123).expect("create faketime file");
-///     faketime_file.to_path_buf()
-/// };
-/// assert!(!path.exists());
+pub fn add_env_key(key: &str) {
+    let mut env_keys = ENV_KEYS.write().unwrap_or_else(PoisonError::into_inner);
+    if key != KEY_FAKETIME && env_keys.iter().all(|existing| existing != key) {
+        env_keys.push(key.to_string());
+    }
+}
+
+/// Enables faketime in current thread and use the specified timestamp file.
+///
+/// The time follows the file even if it goes backward, regardless of the environment variable
+/// `FAKETIME_MONOTONIC`. See `enable_monotonic`.
+///
+/// It accepts `&str`, `String`, `&OsStr`, `OsString`, `&Path` and `PathBuf` alike. The path is
+/// never converted to a string, so the non-UTF-8 paths on unix work as well.
+pub fn enable<T: AsRef<Path>>(path: T) {
+    set_mode(Mode::Path(path.as_ref().to_path_buf()));
+}
+
+/// Enables faketime in current thread and use the specified timestamp file like `enable`, and
+/// guarantees that the file is read afresh on every `unix_time` call.
+///
+/// It is meant for the virtual files, such as the ones backed by FUSE or procfs, whose content
+/// changes on every read while the modification time never updates. The file is neither cached
+/// nor checked by the modification time or the size, so every change is observed.
+///
 /// ```
-pub fn millis_tempfile(millis: u64) -> Result<TempPath> {
-    let path = NamedTempFile::new()?.into_temp_path();
-    write_millis(&path, millis)?;
-    Ok(path)
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable_uncached(&faketime_file);
+/// assert_eq!(1_000, faketime::unix_time_as_millis());
+/// faketime::write_millis(&faketime_file, 2_000).expect("write millis");
+/// assert_eq!(2_000, faketime::unix_time_as_millis());
+/// ```
+///
+/// `enable` does not cache the file either at present. This function states the requirement
+/// explicitly, so the virtual files keep working if `enable` ever trusts the file metadata.
+pub fn enable_uncached<T: AsRef<Path>>(path: T) {
+    set_mode(Mode::Path(path.as_ref().to_path_buf()));
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Enables faketime in current thread and use the specified timestamp file like `enable`, but
+/// only the first call in the thread takes effect.
+///
+/// Unlike `enable`, where the last call wins, the following calls are no-ops, even if they pass
+/// different paths. It guards against double initialization, for example, in the plugin style
+/// code which may be initialized multiple times. Returns whether the call has taken effect.
+///
+/// ```
+/// let first = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// let second = faketime::millis_tempfile(2_000).expect("create faketime file");
+/// assert!(faketime::enable_once(&first));
+/// assert!(!faketime::enable_once(&second));
+/// assert_eq!(1_000, faketime::unix_time_as_millis());
+/// ```
+///
+/// The other functions such as `enable` and `disable` still change the settings. The flag is
+/// only cleared via `clear_all`.
+pub fn enable_once<T: AsRef<Path>>(path: T) -> bool {
+    if ENABLED_ONCE.with(|cell| cell.replace(true)) {
+        return false;
+    }
+    enable(path);
+    true
+}
 
-    #[test]
-    fn test_mock_file_io() {
-        let tempdir = tempfile::tempdir().expect("create tempdir");
-        let faketime_file = tempdir.path().join("faketime");
+/// Enables faketime in current thread and use the specified timestamp file, but the time never
+/// goes backward.
+///
+/// If the file is updated to an earlier time, `unix_time` keeps returning the latest time it has
+/// returned, until the file catches up.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(2_000).expect("create faketime file");
+/// faketime::enable_monotonic(&faketime_file);
+/// assert_eq!(2, faketime::unix_time().as_secs());
+///
+/// faketime::write_millis(&faketime_file, 1_000).expect("write millis");
+/// assert_eq!(2, faketime::unix_time().as_secs());
+/// faketime::write_millis(&faketime_file, 3_000).expect("write millis");
+/// assert_eq!(3, faketime::unix_time().as_secs());
+/// ```
+///
+/// The auto-detected timestamp file is followed in the same way when the environment variable
+/// `FAKETIME_MONOTONIC` is `1`. The floor is dropped when the settings are changed.
+pub fn enable_monotonic<T: AsRef<Path>>(path: T) {
+    set_mode(Mode::Monotonic {
+        path: path.as_ref().to_path_buf(),
+        floor: Duration::ZERO,
+    });
+}
 
-        assert_eq!(None, read_millis(&faketime_file).ok());
-        fs::write(&faketime_file, "x").expect("write millis");
-        assert_eq!(None, read_millis(&faketime_file).ok());
-        fs::write(&faketime_file, "12345\n").expect("write millis");
-        assert_eq!(12345, read_millis(&faketime_file).expect("read millis"));
-        write_millis(&faketime_file, 54321).expect("write millis");
-        assert_eq!(54321, read_millis(&faketime_file).expect("read millis"));
+/// Enables faketime in current thread and use the specified timestamp file, only if the file has
+/// been modified within `max_age`.
+///
+/// A timestamp file left over from a previous run, for example, a CI artifact restored from
+/// cache, would silently apply to the new run and cause confusing failures. This function rejects
+/// such a stale file instead. The age is computed via `age_of_file`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// faketime::enable_fresh(&faketime_file, Duration::from_secs(60)).expect("fresh file");
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// ```
+///
+/// Returns an error and keeps the current settings if the file metadata cannot be read or the file
+/// is older than `max_age`.
+pub fn enable_fresh<T: AsRef<Path>>(path: T, max_age: Duration) -> Result<()> {
+    let path = path.as_ref();
+    let age = age_of_file(path)?;
+    if age > max_age {
+        return Err(Error::other(format!(
+            "stale timestamp file {}: modified {:?} ago",
+            path.display(),
+            age
+        )));
+    }
+    enable(path);
+    Ok(())
+}
+
+/// Gets how long ago the file was modified, which tells whether a timestamp file is fresh.
+///
+/// The age is the real system time minus the file modification time, so it is measured against
+/// the wall clock instead of the faked time. It is zero if the modification time is in the
+/// future.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(0).expect("create faketime file");
+/// assert!(faketime::age_of_file(&faketime_file).expect("file age") < Duration::from_secs(60));
+/// ```
+///
+/// Returns an error if the file metadata cannot be read, or the modification time is before
+/// *UNIX EPOCH*.
+pub fn age_of_file<T: AsRef<Path>>(path: T) -> Result<Duration> {
+    let modified = fs::metadata(path)?
+        .modified()?
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map_err(Error::other)?;
+    Ok(system_unix_time().saturating_sub(modified))
+}
+
+/// Enables faketime in current thread and reads the time from the opened timestamp file.
+///
+/// The file is read from the beginning each time instead of being reopened by path, which saves
+/// the syscalls to resolve the path and open the file.
+///
+/// ```
+/// use std::fs::File;
+///
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// faketime::enable_file(File::open(&faketime_file).expect("open faketime file"));
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// ```
+///
+/// The handle keeps reading the same file even if the path is replaced by another file, for
+/// example, by `write_millis` which writes a temporary file and then renames it to the path. On
+/// unix, a file which has been replaced or removed is detected, and `unix_time` falls back to the
+/// system time like other read errors. On other platforms, such as Windows, the replacement
+/// cannot be detected, and the handle silently keeps reading the stale file. Use `enable`
+/// instead if the timestamp file is updated via atomic replacement, or write to the file in
+/// place.
+pub fn enable_file(file: File) {
+    set_mode(Mode::File(FileHandle::new(file, false)));
+}
+
+/// Enables faketime in current thread and use the specified timestamp file, after checking that
+/// the file can be written via `write_millis` later.
+///
+/// The check creates and removes a temporary file in the parent directory, which is what
+/// `write_millis` needs to replace the timestamp file atomically. Use `enable` if the file is
+/// only read.
+///
+/// ```
+/// let tempdir = tempfile::tempdir().expect("create tempdir");
+/// let faketime_file = tempdir.path().join("faketime");
+/// faketime::enable_writable(&faketime_file).expect("writable");
+/// faketime::set_millis(123_456).expect("set millis");
+/// assert_eq!(123, faketime::unix_time().as_secs());
+///
+/// assert!(faketime::enable_writable("/path/to/missing/faketime").is_err());
+/// ```
+///
+/// Returns an error naming the directory if it does not exist or is not writable. The settings of
+/// current thread are untouched on error.
+pub fn enable_writable<T: AsRef<Path>>(path: T) -> Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    NamedTempFile::new_in(dir).map_err(|err| {
+        Error::new(
+            err.kind(),
+            format!(
+                "directory {} of timestamp file is not writable: {}",
+                dir.display(),
+                err
+            ),
+        )
+    })?;
+    enable(path);
+    Ok(())
+}
+
+/// Enables faketime in current thread and use the specified timestamp file, but falls back to
+/// `default` instead of the system time when the file cannot be read or parsed.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let tempdir = tempfile::tempdir().expect("create tempdir");
+/// let faketime_file = tempdir.path().join("faketime");
+/// faketime::enable_with_default(&faketime_file, Duration::from_secs(1));
+/// assert_eq!(1, faketime::unix_time().as_secs());
+/// faketime::write_millis(&faketime_file, 2_000).expect("write millis");
+/// assert_eq!(2, faketime::unix_time().as_secs());
+/// ```
+pub fn enable_with_default<T: AsRef<Path>>(path: T, default: Duration) {
+    set_mode(Mode::Default {
+        path: path.as_ref().to_path_buf(),
+        default,
+    });
+}
+
+/// Enables faketime in current thread and use the specified timestamp file, after writing
+/// `default_millis` into the file via `write_millis` if the file is missing or cannot be parsed.
+///
+/// Unlike `enable_with_default`, which falls back to the default on every read, it creates the
+/// file, so other threads and processes can follow the same file.
+///
+/// ```
+/// let tempdir = tempfile::tempdir().expect("create tempdir");
+/// let faketime_file = tempdir.path().join("faketime");
+/// faketime::enable_from_file_or_default(&faketime_file, 123_456).expect("create file");
+/// assert_eq!(123_456, faketime::unix_time_as_millis());
+/// assert_eq!(123_456, faketime::validate_file(&faketime_file).expect("valid file"));
+/// ```
+///
+/// Returns an error if the file has to be written but cannot be. The settings of current thread
+/// are untouched on error.
+pub fn enable_from_file_or_default<T: AsRef<Path>>(path: T, default_millis: u64) -> Result<()> {
+    let path = path.as_ref();
+    if read_millis(path).is_err() {
+        write_millis(path, default_millis)?;
+    }
+    enable(path);
+    Ok(())
+}
+
+/// Enables faketime in current thread and use the specified timestamp file, but rounds the time
+/// down to the nearest multiple of `quantum` since *UNIX EPOCH*, to simulate a coarse clock.
+///
+/// For example, a 15ms quantum simulates a system timer with 15ms resolution. The time never
+/// rounds up, so it is never later than the time in the timestamp file. Writing the time via
+/// `set_millis` or `advance` updates the timestamp file without rounding, and the rounding applies
+/// when the time is read.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(1_019).expect("create faketime file");
+/// faketime::enable_quantized(&faketime_file, Duration::from_millis(15));
+/// assert_eq!(1_005, faketime::unix_time_as_millis());
+/// ```
+///
+/// ## Panics
+///
+/// Panics if `quantum` is zero.
+pub fn enable_quantized<T: AsRef<Path>>(path: T, quantum: Duration) {
+    assert!(!quantum.is_zero(), "faketime quantum must not be zero");
+    set_mode(Mode::Quantized {
+        path: path.as_ref().to_path_buf(),
+        quantum,
+    });
+}
+
+/// Enables faketime in current thread and use the last whitespace separated value in the
+/// specified timestamp file as the time, so an append-only log of timestamps works directly.
+///
+/// ```
+/// use std::fs::OpenOptions;
+/// use std::io::Write;
+///
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable_tail(&faketime_file);
+/// let mut log = OpenOptions::new().append(true).open(&faketime_file).expect("open file");
+/// writeln!(log, "\n2000").expect("append value");
+/// assert_eq!(2_000, faketime::unix_time_as_millis());
+/// ```
+///
+/// Each value accepts the same formats as the timestamp file, except that it cannot contain
+/// whitespace, and the checksum line is not supported. The whole file is read on every call of
+/// `unix_time`, so the cost grows with the log. Writing the time via `set_millis` or `advance`
+/// replaces the log with a single value.
+pub fn enable_tail<T: AsRef<Path>>(path: T) {
+    set_mode(Mode::Tail(path.as_ref().to_path_buf()));
+}
+
+fn read_tail_millis(path: &Path) -> Result<u64> {
+    let text = fs::read_to_string(path)?;
+    let value = text
+        .split_whitespace()
+        .last()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "empty timestamp file"))?;
+    parse_millis(value)
+}
+
+fn quantize(time: Duration, quantum: Duration) -> Duration {
+    let nanos = time.as_nanos();
+    let floor = nanos - nanos % quantum.as_nanos();
+    Duration::new(
+        (floor / 1_000_000_000) as u64,
+        (floor % 1_000_000_000) as u32,
+    )
+}
+
+/// Enables faketime in current thread and reads the time from the timestamp file opened in
+/// read-only mode.
+///
+/// The file is opened immediately and read via the handle like `enable_file`. This process must
+/// not change the time in this mode, so the functions which write the timestamp file of current
+/// thread, such as `set_millis` and `advance`, return an error.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// faketime::enable_readonly(&faketime_file).expect("open faketime file");
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// assert!(faketime::set_millis(0).is_err());
+/// ```
+///
+/// Returns an error if the file cannot be opened.
+pub fn enable_readonly<T: AsRef<Path>>(path: T) -> Result<()> {
+    let file = File::open(path)?;
+    set_mode(Mode::File(FileHandle::new(file, true)));
+    Ok(())
+}
+
+/// Enables faketime in current thread and freezes the time in memory.
+///
+/// The time is kept in memory and no timestamp file is involved. Use `set_millis` or `advance`
+/// to change it.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(123_456);
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// faketime::advance(Duration::from_secs(1)).expect("advance");
+/// assert_eq!(124, faketime::unix_time().as_secs());
+/// ```
+pub fn enable_millis(millis: u64) {
+    set_mode(Mode::Frozen(millis_to_duration(millis)));
+}
+
+/// Freezes the time of current thread in memory like `enable_millis`, and also sets the
+/// environment variable `FAKETIME_MILLIS` to `millis`, so the child processes spawned afterward
+/// freeze at the same time via auto-detection.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// faketime::enable_millis_inheritable(123_456);
+/// assert_eq!(Ok("123456".to_string()), std::env::var("FAKETIME_MILLIS"));
+/// # std::env::remove_var("FAKETIME_MILLIS");
+/// # }
+/// ```
+///
+/// The environment variable is process global and it is not restored. It also freezes the time
+/// of the threads in this process which have not decided whether to enable faketime yet, and it
+/// is ignored by a child which has `FAKETIME` in the environment. Mutating the environment is
+/// not thread safe, see `scoped_env`. Use `command_env` instead to configure a single child
+/// without touching the environment of this process.
+pub fn enable_millis_inheritable(millis: u64) {
+    enable_millis(millis);
+    env::set_var(KEY_FAKETIME_MILLIS, millis.to_string());
+}
+
+/// Enables faketime in current thread and shifts the system time by `offset` milliseconds.
+///
+/// Unlike `enable_millis`, the time keeps running along with the system time. A negative
+/// `offset` shifts the time backward, and the result saturates at *UNIX EPOCH*.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let now = faketime::system::unix_time();
+/// faketime::enable_offset(-3_600_000);
+/// assert!(now - faketime::unix_time() > Duration::from_secs(3_500));
+/// ```
+pub fn enable_offset(offset: i64) {
+    set_mode(Mode::Offset(offset));
+}
+
+/// Enables faketime in current thread and replays the time recorded in the log file.
+///
+/// The log file contains one time per line, in the same format as the timestamp file. Blank lines
+/// are ignored. The file is read once when this function is called, then each call of
+/// `unix_time` returns the next value in the log. The argument `end` decides what happens after
+/// the log is exhausted.
+///
+/// ```
+/// use faketime::ReplayEnd;
+/// use std::fs;
+///
+/// let log = tempfile::NamedTempFile::new().expect("create log file");
+/// fs::write(&log, "1000\n2000\n").expect("write log");
+///
+/// faketime::enable_replay(&log, ReplayEnd::Hold).expect("enable replay");
+/// let replayed: Vec<_> = (0..3).map(|_| faketime::unix_time_as_millis()).collect();
+/// assert_eq!(vec![1000, 2000, 2000], replayed);
+///
+/// faketime::enable_replay(&log, ReplayEnd::Loop).expect("enable replay");
+/// let replayed: Vec<_> = (0..3).map(|_| faketime::unix_time_as_millis()).collect();
+/// assert_eq!(vec![1000, 2000, 1000], replayed);
+/// ```
+///
+/// Returns an error if the log file cannot be read, is empty, or contains an invalid line.
+pub fn enable_replay<T: AsRef<Path>>(path: T, end: ReplayEnd) -> Result<()> {
+    let values = fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(parse_millis)
+        .collect::<Result<Vec<_>>>()?;
+    if values.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "replay log is empty"));
+    }
+    set_mode(Mode::Replay(Replay {
+        values,
+        next: 0,
+        end,
+    }));
+    Ok(())
+}
+
+/// Enables faketime in current thread and cycles the time through the values forever.
+///
+/// Each call of `unix_time` returns the next value, and it starts over from the first value after
+/// the last one, like `enable_replay` with `ReplayEnd::Loop`. It is handy to loop through a
+/// repeating pattern in UI and animation tests.
+///
+/// ```
+/// faketime::enable_cycle(vec![1_000, 2_000, 3_000]);
+/// let cycled: Vec<_> = (0..4).map(|_| faketime::unix_time_as_millis()).collect();
+/// assert_eq!(vec![1_000, 2_000, 3_000, 1_000], cycled);
+/// assert_eq!(1, faketime::cycle_position());
+/// ```
+///
+/// ## Panics
+///
+/// Panics if `values` is empty.
+pub fn enable_cycle(values: Vec<u64>) {
+    assert!(!values.is_empty(), "faketime cycle must not be empty");
+    set_mode(Mode::Replay(Replay {
+        values,
+        next: 0,
+        end: ReplayEnd::Loop,
+    }));
+}
+
+/// Enables faketime in current thread and calls the closure to get the time on each `unix_time`
+/// call.
+///
+/// It is the most flexible injection point, the closure can capture states such as an
+/// `Arc<AtomicU64>` or a counter. Like the other `enable_*` functions, it replaces the current
+/// settings of the thread, including the timestamp file and the time in memory, and is replaced
+/// by the next one. The clock installed via `install_as_global_default` still takes precedence,
+/// and the bounds set via `set_bounds` still apply to the returned time.
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::time::Duration;
+///
+/// let counter = Cell::new(0);
+/// faketime::set_clock_closure(Box::new(move || {
+///     counter.set(counter.get() + 1);
+///     Duration::from_secs(counter.get())
+/// }));
+/// assert_eq!(1, faketime::unix_time().as_secs());
+/// assert_eq!(2, faketime::unix_time().as_secs());
+/// ```
+///
+/// The closure must not call the functions of this crate which access the faketime settings of
+/// current thread, such as `unix_time`, otherwise it panics, because the settings are borrowed
+/// while the closure is running.
+pub fn set_clock_closure(closure: Box<dyn Fn() -> Duration + Send>) {
+    set_mode(Mode::Closure(closure));
+}
+
+/// Enables faketime in current thread with the clock registered with the name in
+/// `ClockRegistry::global`.
+///
+/// The clock is resolved by name on every read, so the clock registered later, or a replacement
+/// registered with the same name, takes effect immediately. If no clock is registered with the
+/// name, the system time is used. A test can switch the logical clock of a thread by enabling
+/// another name.
+///
+/// ```
+/// use faketime::clock::{ClockRegistry, FakeClock};
+/// use std::sync::Arc;
+///
+/// ClockRegistry::global().register("doc-a", Arc::new(FakeClock::new(1_000)));
+/// ClockRegistry::global().register("doc-b", Arc::new(FakeClock::new(2_000)));
+/// faketime::enable_named_clock("doc-a");
+/// assert_eq!(1_000, faketime::unix_time_as_millis());
+/// faketime::enable_named_clock("doc-b");
+/// assert_eq!(2_000, faketime::unix_time_as_millis());
+/// ```
+///
+/// The clock is called without borrowing the faketime settings of current thread, so it can call
+/// the functions of this crate, such as `describe`. But a clock which calls `unix_time` in a
+/// thread enabled with the same name recurses infinitely.
+pub fn enable_named_clock(name: impl Into<String>) {
+    set_mode(Mode::Named(name.into()));
+}
+
+/// Pauses the time of current thread at the current faked time, and returns the paused time.
+///
+/// The paused time is truncated to milliseconds unless the time is frozen in memory.
+///
+/// Unlike freezing via `enable_millis`, which discards the running settings, the paused time can
+/// be resumed via `resume`, and it continues from the paused time at the rate before the pause:
+///
+/// - The time scaled via `enable_scaled` is resumed with the same factor.
+/// - The time frozen in memory stays frozen.
+/// - Otherwise, such as the system time, a timestamp file, or an offset, the time flows at the
+///   rate of the real clock, and the accumulated offset to the real time is kept. Note that the
+///   timestamp file is no longer followed after the pause.
+///
+/// A replay log or a jitter sequence is paused at the value which the next `unix_time` would
+/// return, or the base time with jitter, without consuming it.
+///
+/// While paused, the time can still be changed via `set_millis` and `advance`. Pausing the paused
+/// time keeps it paused.
+///
+/// ```
+/// faketime::enable_offset(-3_600_000);
+/// let paused = faketime::pause();
+/// assert_eq!(paused, faketime::unix_time());
+/// faketime::resume().expect("resume");
+/// assert!(faketime::unix_time() - paused < std::time::Duration::from_secs(60));
+/// ```
+pub fn pause() -> Duration {
+    let paused = with_mode(|mode| match mode {
+        Mode::Paused { time, factor } => Some((*time, *factor)),
+        Mode::Frozen(time) => Some((*time, None)),
+        Mode::Scaled(scaled) => Some((scaled.now(), Some(scaled.factor))),
+        _ => None,
+    });
+    // Truncates to milliseconds, so resuming via `Scaled` does not jump backward.
+    let (time, factor) = paused.unwrap_or_else(|| {
+        let millis = Millis::from(thread_peek_time()).0;
+        (millis_to_duration(millis), Some(1.0))
+    });
+    set_mode(Mode::Paused { time, factor });
+    time
+}
+
+/// Resumes the time paused via `pause` in current thread, see `pause`.
+///
+/// Returns an error if the time is not paused in current thread.
+pub fn resume() -> Result<()> {
+    with_mode(|mode| match mode {
+        Mode::Paused { time, factor } => Ok(match factor {
+            Some(factor) => Mode::Scaled(Scaled::new(Millis::from(*time).0, *factor)),
+            None => Mode::Frozen(*time),
+        }),
+        _ => Err(Error::other("faketime is not paused")),
+    })
+    .map(set_mode)
+}
+
+/// Gets the index of the value which the next `unix_time` call returns in current thread, when
+/// faketime is enabled via `enable_cycle` or `enable_replay`.
+///
+/// It returns 0 in the other modes.
+pub fn cycle_position() -> usize {
+    with_mode(|mode| match mode {
+        Mode::Replay(replay) => replay.position(),
+        _ => 0,
+    })
+}
+
+/// Enables faketime in current thread and freezes the time in memory at `time` since
+/// *UNIX EPOCH*, with the full precision of `Duration`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_duration(Duration::new(123, 456_789));
+/// assert_eq!(Duration::new(123, 456_789), faketime::unix_time());
+/// ```
+pub fn enable_duration(time: Duration) {
+    set_mode(Mode::Frozen(time));
+}
+
+/// Enables faketime in current thread and freezes the time in memory at `time`, with the full
+/// precision of `Duration`.
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+///
+/// let time = SystemTime::UNIX_EPOCH + Duration::from_secs(123);
+/// faketime::enable_system_time(time).expect("after UNIX EPOCH");
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// ```
+///
+/// It also accepts `faketime::SystemTime`. Returns an error if `time` is before *UNIX EPOCH*, and
+/// the settings of current thread are untouched.
+pub fn enable_system_time<T: Into<SystemTime>>(time: T) -> Result<()> {
+    let time = time
+        .into()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+    enable_duration(time);
+    Ok(())
+}
+
+/// Enables faketime in current thread and freezes the time in memory at the current system time,
+/// truncated to milliseconds.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_with_now().expect("freeze now");
+/// let now = faketime::unix_time();
+/// assert_eq!(now, faketime::unix_time());
+/// faketime::advance(Duration::from_secs(1)).expect("advance");
+/// assert_eq!(now + Duration::from_secs(1), faketime::unix_time());
+/// ```
+///
+/// Returns an error if the system time in milliseconds overflows `u64`.
+pub fn enable_with_now() -> Result<()> {
+    let millis = duration_to_millis_checked(system_unix_time())
+        .ok_or_else(|| Error::other("faketime overflow"))?;
+    enable_millis(millis);
+    Ok(())
+}
+
+/// Runs the closure with the time frozen at `millis` in current thread, like `enable_millis`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let time = faketime::with_frozen(123_456, || {
+///     faketime::advance(Duration::from_secs(1)).expect("advance");
+///     faketime::unix_time()
+/// });
+/// assert_eq!(Duration::from_millis(124_456), time);
+/// ```
+///
+/// The previous faketime settings of current thread are restored when the closure returns or
+/// panics, so the calls can be nested.
+///
+/// ```
+/// faketime::with_frozen(1_000, || {
+///     faketime::with_frozen(2_000, || assert_eq!(2, faketime::unix_time().as_secs()));
+///     assert_eq!(1, faketime::unix_time().as_secs());
+/// });
+/// ```
+pub fn with_frozen<T, F: FnOnce() -> T>(millis: u64, f: F) -> T {
+    let _guard = ModeGuard::replace(Mode::Frozen(millis_to_duration(millis)));
+    f()
+}
+
+/// Enables faketime in current thread, and the time starts from `base_millis` and runs `factor`
+/// times as fast as the real time.
+///
+/// ```
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// faketime::enable_scaled(1_000, 100.0);
+/// thread::sleep(Duration::from_millis(10));
+/// assert!(faketime::unix_time() >= Duration::from_secs(2));
+/// ```
+///
+/// The elapsed real time is measured by a monotonic clock from this call, so the scaled time never
+/// goes backward. A factor of `0.0` freezes the time at `base_millis`.
+///
+/// The scaled time can be changed via `set_millis` and `advance`, which jump to the new time
+/// immediately, and the time keeps being scaled from there, as if it was enabled again at the new
+/// time.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_scaled(1_000, 10.0);
+/// let jumped = faketime::advance(Duration::from_secs(60)).expect("advance");
+/// assert!(jumped >= 61_000);
+/// assert!(faketime::unix_time_as_millis() >= jumped);
+/// ```
+///
+/// ## Panics
+///
+/// Panics if `factor` is negative, infinite or NaN.
+pub fn enable_scaled(base_millis: u64, factor: f64) {
+    set_mode(Mode::Scaled(Scaled::new(base_millis, factor)));
+}
+
+/// Runs the closure with the time scaled as `enable_scaled`, and restores the previous settings
+/// of current thread afterwards, even if the closure panics.
+///
+/// The real time is measured from entering this function.
+///
+/// ```
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let scaled = faketime::with_scaled(1_000, 100.0, || {
+///     thread::sleep(Duration::from_millis(10));
+///     faketime::unix_time()
+/// });
+/// assert!(scaled >= Duration::from_secs(2));
+/// ```
+///
+/// ## Panics
+///
+/// Panics if `factor` is negative, infinite or NaN.
+pub fn with_scaled<T, F: FnOnce() -> T>(base_millis: u64, factor: f64, f: F) -> T {
+    let _guard = ModeGuard::replace(Mode::Scaled(Scaled::new(base_millis, factor)));
+    f()
+}
+
+/// Creates a scope for spawning scoped threads as `std::thread::scope`, where the time is frozen
+/// at `millis` in both current thread and the spawned threads.
+///
+/// The scoped threads do not carry the `FAKETIME=` name, so they cannot auto-detect the faketime
+/// settings of the parent. The threads spawned via `FaketimeScope::spawn` are frozen at `millis`
+/// before the closure runs. It only covers the threads spawned through the helper, not the ones
+/// spawned via `std::thread` or the plain scope inside the closure.
+///
+/// The previous settings of current thread are restored afterwards as `with_frozen`.
+///
+/// ```
+/// let times = faketime::scope_with_faketime(123_456, |s| {
+///     let first = s.spawn(faketime::unix_time_as_millis);
+///     let second = s.spawn(faketime::unix_time_as_millis);
+///     [first.join().unwrap(), second.join().unwrap()]
+/// });
+/// assert_eq!([123_456, 123_456], times);
+/// ```
+pub fn scope_with_faketime<'env, T, F>(millis: u64, f: F) -> T
+where
+    F: for<'scope> FnOnce(&FaketimeScope<'scope, 'env>) -> T,
+{
+    with_frozen(millis, || {
+        thread::scope(|scope| f(&FaketimeScope { scope, millis }))
+    })
+}
+
+/// A scope to spawn scoped threads which inherit the frozen time, see `scope_with_faketime`.
+pub struct FaketimeScope<'scope, 'env: 'scope> {
+    scope: &'scope thread::Scope<'scope, 'env>,
+    millis: u64,
+}
+
+impl<'scope, 'env> FaketimeScope<'scope, 'env> {
+    /// Spawns a scoped thread as `std::thread::Scope::spawn`, and freezes the time of the thread
+    /// before running the closure.
+    pub fn spawn<T, F>(&self, f: F) -> thread::ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let millis = self.millis;
+        self.scope.spawn(move || {
+            enable_millis(millis);
+            f()
+        })
+    }
+
+    /// Gets the underlying scope to spawn scoped threads without faking the time.
+    pub fn scope(&self) -> &'scope thread::Scope<'scope, 'env> {
+        self.scope
+    }
+}
+
+/// Polls `unix_time` until the returned time satisfies the predicate, and returns that time.
+///
+/// It sleeps `poll` in the real time between the checks, which suits the tests where another
+/// thread or process advances the timestamp file.
+///
+/// ```
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// let path = faketime_file.to_path_buf();
+/// let writer = thread::spawn(move || faketime::write_millis(path, 5_000));
+///
+/// let now = faketime::wait_for(
+///     |now| now >= Duration::from_secs(5),
+///     Duration::from_millis(1),
+///     Duration::from_secs(10),
+/// )
+/// .expect("wait for time");
+/// assert_eq!(Duration::from_secs(5), now);
+/// writer.join().unwrap().expect("write millis");
+/// ```
+///
+/// Returns an error of the kind `ErrorKind::TimedOut` if the predicate still does not hold after
+/// `timeout` in the real time.
+pub fn wait_for<F: Fn(Duration) -> bool>(
+    predicate: F,
+    poll: Duration,
+    timeout: Duration,
+) -> Result<Duration> {
+    let start = system_monotonic();
+    loop {
+        let now = unix_time();
+        if predicate(now) {
+            return Ok(now);
+        }
+        let elapsed = system_monotonic().saturating_sub(start);
+        if elapsed >= timeout {
+            return Err(Error::new(
+                ErrorKind::TimedOut,
+                format!("faketime wait timed out after {:?} at {:?}", timeout, now),
+            ));
+        }
+        thread::sleep(poll.min(timeout - elapsed));
+    }
+}
+
+/// Gets how long the faked time has advanced since faketime was enabled in current thread.
+///
+/// The faked time is recorded whenever the faketime settings of current thread are changed, or
+/// auto-detected. It is saturated at zero if the time has gone backward since then.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_000);
+/// faketime::advance(Duration::from_secs(2)).expect("advance");
+/// assert_eq!(Some(Duration::from_secs(2)), faketime::enabled_since());
+///
+/// faketime::disable();
+/// assert_eq!(None, faketime::enabled_since());
+/// ```
+///
+/// Returns `None` if faketime is not enabled in current thread, or is enabled via
+/// `set_clock_closure` or `enable_named_clock`, because the closure or the clock is not called to
+/// record the time.
+pub fn enabled_since() -> Option<Duration> {
+    let enabled_at = with_mode(|_| ENABLED_AT.with(Cell::get))?;
+    Some(unix_time().saturating_sub(enabled_at))
+}
+
+/// Gets how much real time has passed since faketime was enabled in current thread, measured by
+/// the system monotonic clock.
+///
+/// Unlike `enabled_since`, which measures the faked time, it lets a test correlate the real and
+/// the faked progression, for example, to validate the factor of `enable_scaled`.
+///
+/// ```
+/// faketime::enable_millis(1_000);
+/// let real = faketime::system_elapsed_since_enable().expect("enabled");
+/// assert!(real.as_secs() < 60);
+///
+/// faketime::disable();
+/// assert_eq!(None, faketime::system_elapsed_since_enable());
+/// ```
+///
+/// Returns `None` if faketime is not enabled in current thread.
+pub fn system_elapsed_since_enable() -> Option<Duration> {
+    let enabled_monotonic = with_mode(|_| ENABLED_MONOTONIC.with(Cell::get))?;
+    Some(crate::system::monotonic().saturating_sub(enabled_monotonic))
+}
+
+/// Describes the faketime settings of current thread in a human readable form.
+///
+/// ```
+/// faketime::enable_millis(123_456);
+/// assert_eq!("frozen at 123456ms", faketime::describe());
+/// faketime::disable();
+/// assert_eq!("disabled", faketime::describe());
+/// ```
+///
+/// The settings are auto-detected first if they have not been decided yet.
+pub fn describe() -> String {
+    with_mode(|mode| mode.describe())
+}
+
+/// Installs a panic hook which prints the faketime settings of the panicking thread to stderr,
+/// and then delegates to the previous hook.
+///
+/// Installing it again is a no-op. Call `uninstall_panic_dump` to restore the previous hook.
+///
+/// The hook does not auto-detect the settings, so a thread which has never used faketime is
+/// reported as undecided.
+///
+/// ## Panics
+///
+/// Panics if called from a panicking thread.
+pub fn install_panic_dump() {
+    let mut previous = PREVIOUS_PANIC_HOOK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if previous.is_some() {
+        return;
+    }
+
+    let hook = Arc::new(panic::take_hook());
+    *previous = Some(Arc::clone(&hook));
+    panic::set_hook(Box::new(move |info| {
+        let description = FAKETIME_MODE
+            .try_with(|mode_cell| mode_cell.try_borrow().map(|mode| mode.describe()).ok())
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| "unavailable".to_string());
+        eprintln!("faketime: {}", description);
+        hook(info);
+    }));
+}
+
+/// Restores the panic hook replaced by `install_panic_dump`.
+///
+/// It is a no-op if the panic dump is not installed. Any hook set after `install_panic_dump` is
+/// dropped.
+///
+/// ## Panics
+///
+/// Panics if called from a panicking thread.
+pub fn uninstall_panic_dump() {
+    let previous = PREVIOUS_PANIC_HOOK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(hook) = previous {
+        panic::set_hook(Box::new(move |info| hook(info)));
+    }
+}
+
+/// Exports the faketime settings of current thread to the environment of a child process.
+///
+/// - If faketime is enabled with a timestamp file path, `FAKETIME` is set to the path, and
+///   `FAKETIME_MONOTONIC` is set to `1` if it is enabled via `enable_monotonic`.
+/// - If faketime is disabled, the child uses the system time.
+/// - Otherwise, `FAKETIME_MILLIS` is set to the current faked time, so the child freezes at it.
+///   The replay log and the jitter sequence are not consumed: the value which the next
+///   `unix_time` returns is exported, or the base time with jitter.
+///
+/// The clock installed via `install_as_global_default` is exported as `FAKETIME_MILLIS` as well.
+/// The environment variables inherited from this process which would affect the auto-detection
+/// in the child are removed from the command.
+///
+/// ```
+/// use std::process::Command;
+///
+/// faketime::enable_millis(123_456);
+/// let mut cmd = Command::new("date");
+/// faketime::command_env(&mut cmd);
+/// ```
+pub fn command_env(cmd: &mut Command) {
+    cmd.env_remove(KEY_FAKETIME)
+        .env_remove(KEY_FAKETIME_MILLIS)
+        .env_remove(KEY_FAKETIME_MONOTONIC);
+    for key in ENV_KEYS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        cmd.env_remove(key);
+    }
+
+    if let Some(now) = global_unix_time() {
+        cmd.env(KEY_FAKETIME_MILLIS, Millis::from(now).0.to_string());
+        return;
+    }
+    let exported = with_mode(|mode| match mode {
+        Mode::Path(path) => {
+            cmd.env(KEY_FAKETIME, path);
+            true
+        }
+        Mode::Monotonic { path, .. } => {
+            cmd.env(KEY_FAKETIME, path).env(KEY_FAKETIME_MONOTONIC, "1");
+            true
+        }
+        Mode::Undecided | Mode::Disabled => true,
+        _ => false,
+    });
+    if !exported {
+        let now = Millis::from(thread_peek_time()).0;
+        cmd.env(KEY_FAKETIME_MILLIS, now.to_string());
+    }
+}
+
+/// Enables faketime in current thread and adds deterministic jitter to a base time.
+///
+/// Each call of `unix_time` returns `base_millis` plus a pseudo-random jitter in milliseconds
+/// between 0 and `max_jitter_millis` inclusively. The pseudo-random sequence is generated from
+/// `seed`, so the same seed always produces the same sequence of time.
+///
+/// ```
+/// faketime::enable_jitter(100_000, 42, 500);
+/// let first: Vec<_> = (0..5).map(|_| faketime::unix_time_as_millis()).collect();
+/// assert!(first.iter().all(|millis| (100_000..=100_500).contains(millis)));
+///
+/// faketime::enable_jitter(100_000, 42, 500);
+/// let second: Vec<_> = (0..5).map(|_| faketime::unix_time_as_millis()).collect();
+/// assert_eq!(first, second);
+/// ```
+pub fn enable_jitter(base_millis: u64, seed: u64, max_jitter_millis: u64) {
+    set_mode(Mode::Jitter(Jitter {
+        base_millis,
+        max_jitter_millis,
+        state: seed,
+    }));
+}
+
+/// A snapshot of the faketime settings of current thread, see `snapshot`.
+///
+/// With the cargo feature `serde`, it can be serialized, so a precise fake clock setup can be
+/// shared across tests as a fixture.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaketimeSnapshot {
+    state: SnapshotState,
+    monotonic_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum SnapshotState {
+    Disabled,
+    Path(PathBuf),
+    Monotonic {
+        path: PathBuf,
+        floor: Duration,
+    },
+    Default {
+        path: PathBuf,
+        default: Duration,
+    },
+    Quantized {
+        path: PathBuf,
+        quantum: Duration,
+    },
+    Tail(PathBuf),
+    Frozen(Duration),
+    Offset(i64),
+    Replay {
+        values: Vec<u64>,
+        next: usize,
+        end: ReplayEnd,
+    },
+    Jitter {
+        base_millis: u64,
+        max_jitter_millis: u64,
+        state: u64,
+    },
+    Scaled {
+        base_millis: u64,
+        factor: f64,
+    },
+    Paused {
+        time: Duration,
+        factor: Option<f64>,
+    },
+    Named(String),
+}
+
+/// Captures the faketime settings of current thread, including the timestamp file of `monotonic`.
+///
+/// The snapshot can be reapplied via `restore`, so a test can save the settings, do something
+/// disruptive, and restore them exactly.
+///
+/// ```
+/// faketime::enable_millis(123_456);
+/// let snapshot = faketime::snapshot();
+/// faketime::disable();
+/// faketime::restore(&snapshot);
+/// assert_eq!(123_456, faketime::unix_time_as_millis());
+/// ```
+///
+/// Some settings cannot be captured as they are:
+///
+/// - The file handle of `enable_file` and `enable_readonly` is captured as the time it reads,
+///   which is frozen on restore.
+/// - The closure of `set_clock_closure` is captured as the time it returns, which is frozen on
+///   restore.
+/// - The scaled time of `enable_scaled` is captured as the current scaled time, which starts
+///   flowing again from the restore.
+pub fn snapshot() -> FaketimeSnapshot {
+    let state = with_mode(|mode| match mode {
+        Mode::Undecided | Mode::Disabled => SnapshotState::Disabled,
+        Mode::Path(path) => SnapshotState::Path(path.clone()),
+        Mode::Monotonic { path, floor } => SnapshotState::Monotonic {
+            path: path.clone(),
+            floor: *floor,
+        },
+        Mode::Default { path, default } => SnapshotState::Default {
+            path: path.clone(),
+            default: *default,
+        },
+        Mode::Quantized { path, quantum } => SnapshotState::Quantized {
+            path: path.clone(),
+            quantum: *quantum,
+        },
+        Mode::Tail(path) => SnapshotState::Tail(path.clone()),
+        Mode::File(handle) => SnapshotState::Frozen(or_system(handle.read_millis())),
+        Mode::Closure(closure) => SnapshotState::Frozen(closure()),
+        Mode::Named(name) => SnapshotState::Named(name.clone()),
+        Mode::Paused { time, factor } => SnapshotState::Paused {
+            time: *time,
+            factor: *factor,
+        },
+        Mode::Frozen(time) => SnapshotState::Frozen(*time),
+        Mode::Offset(offset) => SnapshotState::Offset(*offset),
+        Mode::Replay(replay) => SnapshotState::Replay {
+            values: replay.values.clone(),
+            next: replay.next,
+            end: replay.end,
+        },
+        Mode::Jitter(jitter) => SnapshotState::Jitter {
+            base_millis: jitter.base_millis,
+            max_jitter_millis: jitter.max_jitter_millis,
+            state: jitter.state,
+        },
+        Mode::Scaled(scaled) => SnapshotState::Scaled {
+            base_millis: Millis::from(scaled.now()).0,
+            factor: scaled.factor,
+        },
+    });
+    FaketimeSnapshot {
+        state,
+        monotonic_path: MONOTONIC_PATH.with(|cell| cell.borrow().clone()),
+    }
+}
+
+/// Reapplies the faketime settings captured via `snapshot` in current thread.
+///
+/// ## Panics
+///
+/// Panics if the snapshot is deserialized from invalid data, such as an empty replay log or a
+/// negative scale factor.
+pub fn restore(snapshot: &FaketimeSnapshot) {
+    let mode = match &snapshot.state {
+        SnapshotState::Disabled => Mode::Disabled,
+        SnapshotState::Path(path) => Mode::Path(path.clone()),
+        SnapshotState::Monotonic { path, floor } => Mode::Monotonic {
+            path: path.clone(),
+            floor: *floor,
+        },
+        SnapshotState::Default { path, default } => Mode::Default {
+            path: path.clone(),
+            default: *default,
+        },
+        SnapshotState::Quantized { path, quantum } => Mode::Quantized {
+            path: path.clone(),
+            quantum: *quantum,
+        },
+        SnapshotState::Tail(path) => Mode::Tail(path.clone()),
+        SnapshotState::Frozen(time) => Mode::Frozen(*time),
+        SnapshotState::Offset(offset) => Mode::Offset(*offset),
+        SnapshotState::Replay { values, next, end } => {
+            assert!(
+                *next <= values.len() && !values.is_empty(),
+                "invalid faketime replay snapshot"
+            );
+            Mode::Replay(Replay {
+                values: values.clone(),
+                next: *next,
+                end: *end,
+            })
+        }
+        SnapshotState::Jitter {
+            base_millis,
+            max_jitter_millis,
+            state,
+        } => Mode::Jitter(Jitter {
+            base_millis: *base_millis,
+            max_jitter_millis: *max_jitter_millis,
+            state: *state,
+        }),
+        SnapshotState::Scaled {
+            base_millis,
+            factor,
+        } => Mode::Scaled(Scaled::new(*base_millis, *factor)),
+        SnapshotState::Paused { time, factor } => Mode::Paused {
+            time: *time,
+            factor: *factor,
+        },
+        SnapshotState::Named(name) => Mode::Named(name.clone()),
+    };
+    set_mode(mode);
+    MONOTONIC_PATH.with(|cell| *cell.borrow_mut() = snapshot.monotonic_path.clone());
+}
+
+/// Enables faketime in current thread from the command line arguments, and returns whether
+/// faketime is enabled.
+///
+/// The recognized flags are:
+///
+/// - `--faketime-file PATH` or `--faketime-file=PATH` enables faketime with the timestamp file
+///   like `enable`.
+/// - `--faketime-millis N` or `--faketime-millis=N` freezes the time at `N` milliseconds since
+///   *UNIX EPOCH* like `enable_millis`.
+///
+/// The other arguments are ignored. If the flags are given multiple times, the last one wins.
+/// Returns `false` and keeps the current settings if none of the flags is given.
+///
+/// ```
+/// let args: Vec<String> = std::env::args().collect();
+/// faketime::enable_from_args(&args).expect("invalid faketime flags");
+///
+/// assert!(faketime::enable_from_args(&["app", "--faketime-millis", "123456"]).unwrap());
+/// assert_eq!(123_456, faketime::unix_time_as_millis());
+/// ```
+///
+/// ## Errors
+///
+/// Returns an error of `ErrorKind::InvalidInput` if a flag has no value, or the value of
+/// `--faketime-millis` is not an integer. Nothing is enabled on error.
+pub fn enable_from_args<T: AsRef<str>>(args: &[T]) -> Result<bool> {
+    let mut mode = None;
+    let mut args = args.iter().map(AsRef::as_ref);
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None => (arg, None),
+        };
+        if flag != "--faketime-file" && flag != "--faketime-millis" {
+            continue;
+        }
+        let value = inline.or_else(|| args.next()).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} requires a value", flag),
+            )
+        })?;
+        mode = Some(if flag == "--faketime-file" {
+            Mode::Path(PathBuf::from(value))
+        } else {
+            let millis = value.parse().map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid {} {:?}: {}", flag, value, err),
+                )
+            })?;
+            Mode::Frozen(millis_to_duration(millis))
+        });
+    }
+    Ok(mode.map(set_mode).is_some())
+}
+
+/// Disables faketime in current thread.
+pub fn disable() {
+    set_mode(Mode::Disabled);
+}
+
+/// Resets the faketime settings of current thread, which will be auto-detected again on next use.
+///
+/// ```
+/// use std::env;
+///
+/// faketime::disable();
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// env::set_var("FAKETIME", faketime_file.as_os_str());
+///
+/// faketime::reset();
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// ```
+pub fn reset() {
+    set_mode(Mode::Undecided);
+}
+
+/// Resets the faketime settings of all threads, like calling `reset` in every thread.
+///
+/// The threads are not interrupted. Each thread notices the reset and auto-detects the settings
+/// again lazily on its next call which uses the settings, such as `unix_time`. The settings made
+/// explicitly after the reset in a thread, via `enable` for example, are kept.
+pub fn reset_all() {
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Clears all the faketime states of current thread and the process wide ones, as a single call
+/// for test teardown.
+///
+/// It clears the following states of current thread:
+///
+/// - The faketime settings, like `reset`, so they will be auto-detected again on next use.
+/// - The timestamp file of `monotonic`, like `disable_monotonic_file`.
+/// - The time recorded by `poll_changes` and `enabled_since`.
+/// - The flag of `enable_once`.
+///
+/// And the following process wide states:
+///
+/// - The clock installed via `install_as_global_default`.
+/// - The bounds set via `set_bounds`, like `clear_bounds`.
+/// - The timestamp file set via `set_global_path`.
+/// - The environment variable names registered via `add_env_key`.
+/// - The panic hook installed via `install_panic_dump`, like `uninstall_panic_dump`.
+/// - The verification of writes enabled via `set_verify_writes`.
+/// - The format set via `set_write_format`.
+///
+/// The environment variables are untouched, and the settings of other threads are kept until
+/// they are reset, see `reset_all`. The clock installed via `install_as_global_default` is
+/// uninstalled and dropped once no thread is reading it, see there.
+///
+/// ```
+/// faketime::enable_millis(123_456);
+/// faketime::set_global_path("/path/to/faketime");
+/// faketime::clear_all();
+/// assert_ne!(123_456, faketime::unix_time_as_millis());
+/// ```
+///
+/// ## Panics
+///
+/// Panics if called from a panicking thread while the panic dump is installed.
+pub fn clear_all() {
+    clear_all_but_panic_dump();
+    uninstall_panic_dump();
+}
+
+fn clear_all_but_panic_dump() {
+    reset();
+    disable_monotonic_file();
+    LAST_POLLED.with(|cell| cell.set(None));
+    ENABLED_AT.with(|cell| cell.set(None));
+    ENABLED_MONOTONIC.with(|cell| cell.set(None));
+    ENABLED_ONCE.with(|cell| cell.set(false));
+
+    global_clock::store(None);
+    clear_bounds();
+    *GLOBAL_PATH.write().unwrap_or_else(PoisonError::into_inner) = None;
+    ENV_KEYS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+    set_verify_writes(false);
+    *WRITE_FORMAT.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Creates a guard which clears all the faketime states like `clear_all` if current thread panics
+/// while the guard is alive.
+///
+/// Create it at the entry of a test, so a panicking test does not leave the faketime states
+/// dirty for the next test which reuses the thread. Nothing is cleared when the guard is dropped
+/// without a panic.
+///
+/// ```
+/// use std::panic;
+///
+/// let result = panic::catch_unwind(|| {
+///     let _guard = faketime::reset_on_panic();
+///     faketime::enable_millis(123_456);
+///     panic!("test failure");
+/// });
+/// assert!(result.is_err());
+/// assert_eq!("disabled", faketime::describe());
+/// ```
+///
+/// The panic hook installed via `install_panic_dump` is kept, because the panic hook cannot be
+/// changed while panicking.
+pub fn reset_on_panic() -> ResetOnPanic {
+    ResetOnPanic { _private: () }
+}
+
+/// Clears all the faketime states on panic. See `reset_on_panic`.
+#[must_use = "nothing is cleared on panic if the guard is not kept"]
+pub struct ResetOnPanic {
+    _private: (),
+}
+
+impl Drop for ResetOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            clear_all_but_panic_dump();
+        }
+    }
+}
+
+/// Sets the environment variable `FAKETIME` to the specified timestamp file, and restores the
+/// previous value when the returned guard is dropped.
+///
+/// Threads which have not decided whether to enable faketime yet will pick up the file via
+/// auto-detection.
+///
+/// ```
+/// use std::thread;
+///
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// let _guard = faketime::scoped_env(&faketime_file);
+///
+/// thread::spawn(|| assert_eq!(123, faketime::unix_time().as_secs()))
+///     .join()
+///     .expect("join thread");
+/// ```
+///
+/// Environment variables are process global, and mutating them is not thread safe. Avoid using
+/// this function while other threads may read or write the environment, for example, in tests
+/// running in parallel which also depend on `FAKETIME`.
+pub fn scoped_env<T: AsRef<Path>>(path: T) -> EnvGuard {
+    EnvGuard::set(KEY_FAKETIME, path.as_ref().as_os_str())
+}
+
+/// Sets the environment variable `key` to `value`, runs `f`, and restores the previous value of
+/// `key` when `f` returns or panics.
+///
+/// It works for any of the environment variables read by the auto-detection, such as `FAKETIME`,
+/// `FAKETIME_MILLIS` and `FAKETIME_MONOTONIC`. Threads which have not decided whether to enable
+/// faketime yet, including the threads spawned in `f`, pick up the value via auto-detection.
+///
+/// ```
+/// use std::thread;
+///
+/// # #[cfg(not(disable_faketime))]
+/// let secs = faketime::with_env("FAKETIME_MILLIS", "123456", || {
+///     thread::spawn(|| faketime::unix_time().as_secs())
+///         .join()
+///         .expect("join thread")
+/// });
+/// # #[cfg(not(disable_faketime))]
+/// assert_eq!(123, secs);
+/// ```
+///
+/// **The environment is process global, and mutating it is not thread safe.** Every thread in the
+/// process observes the value while `f` runs, and concurrent reads or writes of the environment
+/// in other threads, for example, in tests running in parallel, may race or observe the wrong
+/// value. Serialize the tests which use this function.
+pub fn with_env<T, F: FnOnce() -> T>(key: &str, value: impl AsRef<OsStr>, f: F) -> T {
+    let _guard = EnvGuard::set(key, value.as_ref());
+    f()
+}
+
+/// Restores an environment variable on drop. See `scoped_env`.
+#[must_use = "the environment variable is restored immediately if the guard is not kept"]
+pub struct EnvGuard {
+    key: String,
+    previous: Option<OsString>,
+}
+
+impl EnvGuard {
+    fn set(key: &str, value: &OsStr) -> EnvGuard {
+        let previous = env::var_os(key);
+        env::set_var(key, value);
+        EnvGuard {
+            key: key.to_string(),
+            previous,
+        }
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(value) => env::set_var(&self.key, value),
+            None => env::remove_var(&self.key),
+        }
+    }
+}
+
+/// Reads and parses the timestamp file, and returns the time in milliseconds since *UNIX EPOCH*.
+///
+/// It checks the file without any side effect: no fallback to the system time, and the faketime
+/// settings of current thread are untouched.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// assert_eq!(123_456, faketime::validate_file(&faketime_file).expect("valid file"));
+/// assert!(faketime::validate_file("/path/to/missing/faketime").is_err());
+/// ```
+///
+/// The returned error message contains the file path and the cause.
+pub fn validate_file<T: AsRef<Path>>(path: T) -> Result<u64> {
+    let path = path.as_ref();
+    read_millis(path).map_err(|err| {
+        Error::new(
+            err.kind(),
+            format!("invalid timestamp file {}: {}", path.display(), err),
+        )
+    })
+}
+
+/// The latest time accepted by `sanity_check`, 3000-01-01T00:00:00Z in milliseconds since
+/// *UNIX EPOCH*.
+pub const SANE_MAX_MILLIS: u64 = 32_503_680_000_000;
+
+/// The earliest time accepted by `sanity_check` in milliseconds since *UNIX EPOCH*, which rejects
+/// only zero.
+pub const SANE_MIN_MILLIS: u64 = 1;
+
+/// Checks that the timestamp file holds a plausible time, between `SANE_MIN_MILLIS` and
+/// `SANE_MAX_MILLIS` inclusively.
+///
+/// A zero or a time after the year 3000 usually indicates a bug in the writer, such as writing an
+/// uninitialized value, or nanoseconds where milliseconds are expected. Like `validate_file`, it
+/// has no side effect.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(1_577_880_000_000).expect("create faketime file");
+/// assert!(faketime::sanity_check(&faketime_file).is_ok());
+/// faketime::write_millis(&faketime_file, 0).expect("write millis");
+/// assert!(faketime::sanity_check(&faketime_file).is_err());
+/// ```
+///
+/// Returns an error of `ErrorKind::InvalidData` if the time is implausible, or the error of
+/// `validate_file` if the file cannot be read or parsed.
+pub fn sanity_check<T: AsRef<Path>>(path: T) -> Result<()> {
+    let path = path.as_ref();
+    let millis = validate_file(path)?;
+    if (SANE_MIN_MILLIS..=SANE_MAX_MILLIS).contains(&millis) {
+        return Ok(());
+    }
+    let reason = if millis < SANE_MIN_MILLIS {
+        "is zero"
+    } else {
+        "is after the year 3000"
+    };
+    Err(Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "implausible timestamp file {}: {}ms {}",
+            path.display(),
+            millis,
+            reason
+        ),
+    ))
+}
+
+/// Parses the text exactly as the content of a timestamp file, see the module document for the
+/// accepted formats.
+///
+/// It guarantees that the command line arguments such as `--faketime=...` are interpreted the
+/// same way as the timestamp files.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// assert_eq!(
+///     Duration::from_secs(1_577_836_800),
+///     faketime::from_millis_str("2020-01-01\n").expect("parse time")
+/// );
+/// assert_eq!(
+///     Duration::from_millis(1_500),
+///     faketime::from_millis_str("s:1.5").expect("parse time")
+/// );
+/// assert!(faketime::from_millis_str("invalid").is_err());
+/// ```
+pub fn from_millis_str(text: &str) -> Result<Duration> {
+    parse_contents(text).map(millis_to_duration)
+}
+
+pub(crate) fn read_millis<T: AsRef<Path>>(path: T) -> Result<u64> {
+    fs::read_to_string(path).and_then(|text| parse_contents(&text))
+}
+
+/// Parses the content of the timestamp file, which has the time in the first line, and an
+/// optional CRC32 of the first line in hex in the second line.
+fn parse_contents(text: &str) -> Result<u64> {
+    let text = text.trim();
+    match text.split_once('\n') {
+        Some((value, checksum)) => {
+            let value = value.trim();
+            let checksum = u32::from_str_radix(checksum.trim(), 16).map_err(Error::other)?;
+            if crc32(value.as_bytes()) != checksum {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "timestamp file checksum mismatch",
+                ));
+            }
+            parse_millis(value)
+        }
+        None => parse_millis(text),
+    }
+}
+
+/// Computes the CRC-32 (IEEE 802.3) checksum.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn parse_millis(text: &str) -> Result<u64> {
+    if let Some(secs) = text.strip_prefix(PREFIX_SECONDS) {
+        return parse_seconds_millis(secs.trim())
+            .ok_or_else(|| Error::other(format!("invalid seconds: {}", secs)));
+    }
+    if let Some(nanos) = text.strip_prefix(PREFIX_NANOS) {
+        return nanos
+            .trim()
+            .parse::<u128>()
+            .ok()
+            .and_then(|nanos| u64::try_from(nanos / 1_000_000).ok())
+            .ok_or_else(|| Error::other(format!("invalid nanoseconds: {}", nanos)));
+    }
+    text.parse().or_else(|err| {
+        let parsed = if text.contains('T') && text.contains(':') {
+            parse_rfc3339_millis(text)
+        } else {
+            parse_date_millis(text).or_else(|| parse_float_millis(text))
+        };
+        parsed.ok_or_else(|| Error::other(err))
+    })
+}
+
+/// Parses the seconds, which is either an integer or a floating point number, as milliseconds.
+///
+/// A decimal such as `1577836800.123` is parsed exactly, the digits beyond milliseconds are
+/// truncated.
+fn parse_seconds_millis(text: &str) -> Option<u64> {
+    if let Ok(secs) = text.parse::<u64>() {
+        return secs.checked_mul(1_000);
+    }
+    match text.split_once('.') {
+        Some((secs, fraction))
+            if !secs.is_empty()
+                && !fraction.is_empty()
+                && secs
+                    .bytes()
+                    .chain(fraction.bytes())
+                    .all(|b| b.is_ascii_digit()) =>
+        {
+            let fraction_millis = fraction
+                .bytes()
+                .chain(std::iter::repeat(b'0'))
+                .take(3)
+                .fold(0, |millis, digit| millis * 10 + u64::from(digit - b'0'));
+            secs.parse::<u64>()
+                .ok()?
+                .checked_mul(1_000)?
+                .checked_add(fraction_millis)
+        }
+        _ => float_to_millis(text.parse::<f64>().ok()? * 1_000.0),
+    }
+}
+
+/// Parses the floating point number and rounds it to the nearest milliseconds.
+fn parse_float_millis(text: &str) -> Option<u64> {
+    float_to_millis(text.parse::<f64>().ok()?)
+}
+
+fn float_to_millis(millis: f64) -> Option<u64> {
+    let millis = millis.round();
+    // `u64::MAX as f64` is rounded up to 2^64, which is out of range.
+    if millis.is_finite() && millis >= 0.0 && millis < u64::MAX as f64 {
+        Some(millis as u64)
+    } else {
+        None
+    }
+}
+
+/// Parses date `YYYY-MM-DD` as the milliseconds since UNIX EPOCH at the midnight in UTC.
+fn parse_date_millis(text: &str) -> Option<u64> {
+    let days = parse_date_days(text)?;
+    if days < 0 {
+        return None;
+    }
+    Some(days as u64 * 86_400_000)
+}
+
+/// Parses RFC 3339 timestamp such as `2020-01-01T12:00:00.500+08:00` as the milliseconds since
+/// UNIX EPOCH. The fraction of second is truncated to milliseconds.
+fn parse_rfc3339_millis(text: &str) -> Option<u64> {
+    let days = parse_date_days(text.get(..10)?)?;
+    if text.get(10..11)? != "T" {
+        return None;
+    }
+    let hour = parse_digits(text.get(11..13)?, 2)?;
+    let minute = parse_digits(text.get(14..16)?, 2)?;
+    let second = parse_digits(text.get(17..19)?, 2)?;
+    if text.get(13..14)? != ":"
+        || text.get(16..17)? != ":"
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = text.get(19..)?;
+    let mut fraction_millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        for (i, digit) in fraction.bytes().take(3).enumerate() {
+            if i < digits {
+                fraction_millis += i64::from(digit - b'0') * [100, 10, 1][i];
+            }
+        }
+        rest = &fraction[digits..];
+    }
+
+    let offset_minutes = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let offset_hour = parse_digits(rest.get(1..3)?, 2)?;
+            let offset_minute = parse_digits(rest.get(4..)?, 2)?;
+            if rest.get(3..4)? != ":" || offset_hour > 23 || offset_minute > 59 {
+                return None;
+            }
+            sign * (offset_hour * 60 + offset_minute)
+        }
+    };
+
+    let secs = days * 86_400 + hour * 3_600 + minute * 60 + second - offset_minutes * 60;
+    let millis = secs.checked_mul(1_000)? + fraction_millis;
+    if millis < 0 {
+        return None;
+    }
+    Some(millis as u64)
+}
+
+/// Parses date `YYYY-MM-DD` as the days since UNIX EPOCH.
+fn parse_date_days(text: &str) -> Option<i64> {
+    let mut parts = text.split('-');
+    let year = parse_digits(parts.next()?, 4)?;
+    let month = parse_digits(parts.next()?, 2)?;
+    let day = parse_digits(parts.next()?, 2)?;
+    if parts.next().is_some()
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+    {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day))
+}
+
+fn parse_digits(text: &str, len: usize) -> Option<i64> {
+    if text.len() == len && text.bytes().all(|b| b.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Counts days since 1970-01-01 in the proleptic Gregorian calendar.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Converts the days since UNIX EPOCH to the date `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Formats the milliseconds since UNIX EPOCH as a RFC 3339 timestamp in UTC, or returns `None` if
+/// the year is beyond 9999.
+fn format_rfc3339(millis: u64) -> Option<String> {
+    let days = i64::try_from(millis / 86_400_000).ok()?;
+    let (year, month, day) = civil_from_days(days);
+    if year > 9999 {
+        return None;
+    }
+    let millis_of_day = millis % 86_400_000;
+    let secs = millis_of_day / 1_000;
+    let fraction = match millis_of_day % 1_000 {
+        0 => String::new(),
+        millis => format!(".{:03}", millis),
+    };
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60,
+        fraction
+    ))
+}
+
+pub(crate) fn read_or_system<T: AsRef<Path>>(path: T) -> Duration {
+    read_or_system_reported(path).0
+}
+
+/// Reads the time from the timestamp file, and falls back to the system time when an error
+/// occurs, as `unix_time` does. The returned flag tells whether the time is read from the file.
+///
+/// It lets the diagnostic wrappers act on the fallback without installing a global hook.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// let (time, from_file) = faketime::read_or_system_reported(&faketime_file);
+/// assert_eq!((123, true), (time.as_secs(), from_file));
+///
+/// let (_, from_file) = faketime::read_or_system_reported("/path/to/missing/faketime");
+/// assert!(!from_file);
+/// ```
+pub fn read_or_system_reported<T: AsRef<Path>>(path: T) -> (Duration, bool) {
+    match record_read(|| read_millis(path)) {
+        Ok(millis) => (millis_to_duration(millis), true),
+        err => (or_system(err), false),
+    }
+}
+
+/// Reads the time from the timestamp file, and falls back to `default` instead of the system
+/// time when the file cannot be read or parsed.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// let default = Duration::from_secs(1);
+/// assert_eq!(
+///     Duration::from_millis(123_456),
+///     faketime::read_or_default(&faketime_file, default)
+/// );
+/// assert_eq!(default, faketime::read_or_default("/path/to/missing/faketime", default));
+/// ```
+pub fn read_or_default<T: AsRef<Path>>(path: T, default: Duration) -> Duration {
+    or_else(record_read(|| read_millis(path)), || default)
+}
+
+fn or_system(result: Result<u64>) -> Duration {
+    or_else(result, system_unix_time)
+}
+
+fn or_else<F: FnOnce() -> Duration>(result: Result<u64>, fallback: F) -> Duration {
+    match result {
+        Ok(millis) => millis_to_duration(millis),
+        Err(_) => {
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_fallback();
+            fallback()
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn record_read<F: FnOnce() -> Result<u64>>(read: F) -> Result<u64> {
+    let start = system_monotonic();
+    let result = read();
+    crate::metrics::record_read(system_monotonic() - start);
+    result
+}
+
+#[cfg(not(feature = "metrics"))]
+fn record_read<F: FnOnce() -> Result<u64>>(read: F) -> Result<u64> {
+    read()
+}
+
+/// Writes time as milliseconds since *UNIX EPOCH* into the specified timestamp file.
+///
+/// If the verification is enabled via `set_verify_writes`, returns an error when the written file
+/// does not read back as `millis`.
+pub fn write_millis<T: AsRef<Path>>(path: T, millis: u64) -> Result<()> {
+    let mut file = NamedTempFile::new()?;
+    file.write_all(write_format().format(millis).as_bytes())?;
+    file.into_temp_path().persist(&path)?;
+    verify_write(path.as_ref(), millis)
+}
+
+/// Writes time as nanoseconds since *UNIX EPOCH* with the `ns:` prefix into the specified
+/// timestamp file, keeping the full precision of `duration`.
+///
+/// The format set via `set_write_format` does not apply. The file can be read back exactly via
+/// `read_duration`, while `enable` and the other readers truncate the time to milliseconds.
+pub fn write_duration<T: AsRef<Path>>(path: T, duration: Duration) -> Result<()> {
+    let mut file = NamedTempFile::new()?;
+    write!(file, "{}{}", PREFIX_NANOS, duration.as_nanos())?;
+    file.into_temp_path().persist(&path)?;
+    verify_write(path.as_ref(), Millis::from(duration).0)
+}
+
+/// Reads the time from the timestamp file, keeping the full precision of the `ns:` format.
+///
+/// The other formats are read as milliseconds like `validate_file`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(1_500).expect("create faketime file");
+/// let time = faketime::read_duration(&faketime_file).expect("read duration");
+/// assert_eq!(Duration::from_millis(1_500), time);
+/// ```
+pub fn read_duration<T: AsRef<Path>>(path: T) -> Result<Duration> {
+    let text = fs::read_to_string(path)?;
+    let millis = parse_contents(&text)?;
+    let value = text.trim().lines().next().unwrap_or_default().trim();
+    Ok(match value.strip_prefix(PREFIX_NANOS) {
+        Some(nanos) => {
+            let nanos: u128 = nanos.trim().parse().map_err(Error::other)?;
+            Duration::new(
+                (nanos / 1_000_000_000) as u64,
+                (nanos % 1_000_000_000) as u32,
+            )
+        }
+        None => millis_to_duration(millis),
+    })
+}
+
+/// The format of the timestamp files written by this crate, see `set_write_format`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WriteFormat {
+    /// Milliseconds since *UNIX EPOCH*, such as `1577880000500`.
+    #[default]
+    Millis,
+    /// Seconds since *UNIX EPOCH* with the `s:` prefix, such as `s:1577880000.5`.
+    Seconds,
+    /// RFC 3339 timestamp in UTC, such as `2020-01-01T12:00:00.500Z`. The time after the year
+    /// 9999 is written as milliseconds instead.
+    Rfc3339,
+    /// Nanoseconds since *UNIX EPOCH* with the `ns:` prefix, such as `ns:1577880000500000000`.
+    NanosPrefixed,
+}
+
+impl WriteFormat {
+    fn from_env_value(value: &str) -> Option<WriteFormat> {
+        match value.trim() {
+            "millis" => Some(WriteFormat::Millis),
+            "seconds" => Some(WriteFormat::Seconds),
+            "rfc3339" => Some(WriteFormat::Rfc3339),
+            "nanos" => Some(WriteFormat::NanosPrefixed),
+            _ => None,
+        }
+    }
+
+    fn format(self, millis: u64) -> String {
+        match self {
+            WriteFormat::Millis => millis.to_string(),
+            WriteFormat::Seconds => match millis % 1_000 {
+                0 => format!("{}{}", PREFIX_SECONDS, millis / 1_000),
+                fraction => {
+                    let fraction = format!("{:03}", fraction);
+                    format!(
+                        "{}{}.{}",
+                        PREFIX_SECONDS,
+                        millis / 1_000,
+                        fraction.trim_end_matches('0')
+                    )
+                }
+            },
+            WriteFormat::Rfc3339 => format_rfc3339(millis).unwrap_or_else(|| millis.to_string()),
+            WriteFormat::NanosPrefixed => {
+                format!("{}{}", PREFIX_NANOS, u128::from(millis) * 1_000_000)
+            }
+        }
+    }
+}
+
+/// Writes the times into the timestamp files, such as the clocks of several nodes, via
+/// `write_millis`.
+///
+/// Each file is written atomically, but the files are not updated together atomically, which is
+/// not achievable. They are written one by one in the order of `entries`, on a best-effort basis.
+///
+/// ```
+/// let first = faketime::millis_tempfile(0).expect("create faketime file");
+/// let second = faketime::millis_tempfile(0).expect("create faketime file");
+/// faketime::write_millis_all(&[(&first, 1_000), (&second, 2_000)]).expect("write millis");
+/// assert_eq!(2_000, faketime::validate_file(&second).expect("valid file"));
+/// ```
+///
+/// Returns on the first error, whose message tells the failed file and how many files before it
+/// have been written. The files after it are left untouched.
+pub fn write_millis_all<T: AsRef<Path>>(entries: &[(T, u64)]) -> Result<()> {
+    for (written, (path, millis)) in entries.iter().enumerate() {
+        write_millis(path, *millis).map_err(|err| {
+            Error::new(
+                err.kind(),
+                format!(
+                    "failed to write faketime file {} after {} of {} files were written: {}",
+                    path.as_ref().display(),
+                    written,
+                    entries.len(),
+                    err
+                ),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Sets the format of the timestamp files written by this crate process wide.
+///
+/// It applies to `write_millis`, `write`, `write_millis_checksummed`, `Writer::set` and the
+/// functions based on them, such as `millis_tempfile` and `set_millis`. Before it is called, the
+/// format is read from the environment variable `FAKETIME_FMT`, which is one of `millis`,
+/// `seconds`, `rfc3339` and `nanos`, and defaults to `WriteFormat::Millis`.
+///
+/// ```
+/// use faketime::WriteFormat;
+///
+/// faketime::set_write_format(WriteFormat::Rfc3339);
+/// let faketime_file = faketime::millis_tempfile(1_577_880_000_500).expect("create faketime file");
+/// assert_eq!(
+///     "2020-01-01T12:00:00.500Z",
+///     std::fs::read_to_string(&faketime_file).expect("read file")
+/// );
+/// assert_eq!(1_577_880_000_500, faketime::validate_file(&faketime_file).expect("valid file"));
+/// faketime::set_write_format(WriteFormat::Millis);
+/// ```
+pub fn set_write_format(format: WriteFormat) {
+    *WRITE_FORMAT.write().unwrap_or_else(PoisonError::into_inner) = Some(format);
+}
+
+fn write_format() -> WriteFormat {
+    let format = *WRITE_FORMAT.read().unwrap_or_else(PoisonError::into_inner);
+    format.unwrap_or_else(|| {
+        env::var(KEY_FAKETIME_FMT)
+            .ok()
+            .and_then(|value| WriteFormat::from_env_value(&value))
+            .unwrap_or_default()
+    })
+}
+
+/// Sets whether the timestamp files written by this crate are read back and verified.
+///
+/// It is disabled by default to avoid the extra read. Once enabled, `write_millis`,
+/// `write_millis_checksummed`, `Writer::set` and the functions based on them, such as
+/// `millis_tempfile` and `set_millis`, read the file after writing it, and return an error if it
+/// does not parse to the written time. It catches the file systems which silently corrupt the
+/// writes, which would otherwise show up later as a confusing fallback to the system time.
+///
+/// ```
+/// faketime::set_verify_writes(true);
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// assert_eq!(123_456, faketime::validate_file(&faketime_file).expect("valid file"));
+/// faketime::set_verify_writes(false);
+/// ```
+pub fn set_verify_writes(verify: bool) {
+    VERIFY_WRITES.store(verify, Ordering::Release);
+}
+
+fn verify_write(path: &Path, millis: u64) -> Result<()> {
+    if VERIFY_WRITES.load(Ordering::Acquire) {
+        read_back(path, millis)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the timestamp file reads back as `millis`.
+fn read_back(path: &Path, millis: u64) -> Result<()> {
+    match read_millis(path) {
+        Ok(read) if read == millis => Ok(()),
+        Ok(read) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "faketime file {} reads back {} instead of the written {}",
+                path.display(),
+                read,
+                millis
+            ),
+        )),
+        Err(err) => Err(Error::new(
+            err.kind(),
+            format!(
+                "faketime file {} cannot be read back: {}",
+                path.display(),
+                err
+            ),
+        )),
+    }
+}
+
+/// Writes time as milliseconds since *UNIX EPOCH* into the specified timestamp file, followed by
+/// a line of the CRC32 checksum.
+///
+/// The reader verifies the checksum and treats a mismatch as an invalid file, which guards
+/// against torn reads when the file is not replaced atomically, for example, when it is written
+/// by another tool in place. This function itself still writes the file atomically.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(0).expect("create faketime file");
+/// faketime::write_millis_checksummed(&faketime_file, 123_456).expect("write millis");
+/// assert_eq!(
+///     "123456\n0972d361\n",
+///     std::fs::read_to_string(&faketime_file).expect("read file")
+/// );
+/// assert_eq!(123_456, faketime::validate_file(&faketime_file).expect("valid file"));
+/// ```
+pub fn write_millis_checksummed<T: AsRef<Path>>(path: T, millis: u64) -> Result<()> {
+    let value = write_format().format(millis);
+    let mut file = NamedTempFile::new()?;
+    write!(file, "{}\n{:08x}\n", value, crc32(value.as_bytes()))?;
+    file.into_temp_path().persist(&path)?;
+    verify_write(path.as_ref(), millis)
+}
+
+/// Writes the timestamp file repeatedly, reusing the same temporary file.
+///
+/// `write_millis` creates a new temporary file with a random name for every write. The writer
+/// instead picks a temporary file path in the same directory as the timestamp file once, then
+/// each `set` creates the file exclusively at that path, writes it, and renames it to the
+/// timestamp file. The update is still atomic.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(0).expect("create faketime file");
+/// let mut writer = faketime::Writer::new(&faketime_file).expect("create writer");
+/// faketime::enable(&faketime_file);
+/// for millis in 1..=100 {
+///     writer.set(millis).expect("write millis");
+/// }
+/// assert_eq!(100, faketime::unix_time_as_millis());
+/// ```
+///
+/// Only one writer should write the same timestamp file at the same time.
+#[derive(Debug)]
+pub struct Writer {
+    path: PathBuf,
+    temp_path: PathBuf,
+    /// The temporary file which still exists at `temp_path`, it's `None` after it has been
+    /// renamed to `path`.
+    temp_file: Option<File>,
+}
+
+impl Writer {
+    /// Creates a writer for the timestamp file.
+    ///
+    /// Returns an error if the temporary file cannot be created in the directory of `path`.
+    pub fn new<T: AsRef<Path>>(path: T) -> Result<Writer> {
+        let path = path.as_ref().to_path_buf();
+        let (temp_file, temp_path) = reserve_temp_file(&path)?;
+        Ok(Writer {
+            path,
+            temp_path,
+            temp_file: Some(temp_file),
+        })
+    }
+
+    /// Writes time as milliseconds since *UNIX EPOCH* into the timestamp file.
+    pub fn set(&mut self, millis: u64) -> Result<()> {
+        let mut file = match self.temp_file.take() {
+            Some(file) => file,
+            None => self.recreate_temp_file()?,
+        };
+        let written = file
+            .write_all(write_format().format(millis).as_bytes())
+            .and_then(|_| fs::rename(&self.temp_path, &self.path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&self.temp_path);
+            return Err(err);
+        }
+        verify_write(&self.path, millis)
+    }
+
+    /// Creates the temporary file again after it has been renamed. The path is no longer reserved
+    /// after the rename, so it is created exclusively, and another path is reserved if somebody
+    /// else has taken it.
+    fn recreate_temp_file(&mut self) -> Result<File> {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.temp_path)
+        {
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                let (file, temp_path) = reserve_temp_file(&self.path)?;
+                self.temp_path = temp_path;
+                Ok(file)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Creates a temporary file in the directory of the timestamp file, and keeps it after the handle
+/// is dropped.
+fn reserve_temp_file(path: &Path) -> Result<(File, PathBuf)> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    NamedTempFile::new_in(dir)?.keep().map_err(|err| err.error)
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        if self.temp_file.is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Time in milliseconds since *UNIX EPOCH*, which can be converted from `u64` millis or a
+/// `Duration`.
+///
+/// The conversion from `Duration` truncates the sub-millisecond part, and saturates at
+/// `u64::MAX` milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Millis(pub u64);
+
+impl From<u64> for Millis {
+    fn from(millis: u64) -> Self {
+        Millis(millis)
+    }
+}
+
+impl From<Duration> for Millis {
+    fn from(duration: Duration) -> Self {
+        Millis(duration_to_millis_checked(duration).unwrap_or(u64::MAX))
+    }
+}
+
+/// Writes time into the specified timestamp file, which accepts both `u64` millis and
+/// `Duration`.
+///
+/// The file stores whole milliseconds, so the sub-millisecond part of a `Duration` is truncated.
+/// See `Millis`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(0).expect("create faketime file");
+/// faketime::write(&faketime_file, Duration::from_micros(123_456_789)).expect("write time");
+/// assert_eq!(123_456, faketime::validate_file(&faketime_file).expect("read time"));
+/// faketime::write(&faketime_file, 123_u64).expect("write time");
+/// assert_eq!(123, faketime::validate_file(&faketime_file).expect("read time"));
+/// ```
+pub fn write<T: AsRef<Path>, M: Into<Millis>>(path: T, time: M) -> Result<()> {
+    write_millis(path, time.into().0)
+}
+
+/// Writes time as milliseconds since *UNIX EPOCH* into the timestamp file of current thread, or
+/// sets the time in memory if faketime is enabled via `enable_millis`.
+///
+/// Returns the previous time in milliseconds. It is the system time if the timestamp file could
+/// not be read, which is also what `unix_time` would have returned.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert_eq!(1_000, faketime::set_millis(123_456).expect("set millis"));
+/// assert_eq!(123, faketime::unix_time().as_secs());
+/// ```
+///
+/// Returns an error if faketime is neither enabled with a timestamp file path nor kept in memory
+/// via `enable_millis` or `enable_scaled` in current thread.
+pub fn set_millis(millis: u64) -> Result<u64> {
+    with_mode(|mode| mode.set_time(millis_to_duration(millis)))
+        .map(|previous| Millis::from(previous).0)
+}
+
+/// Sets the time like `set_millis`, but only if `millis` is greater than the current time, so the
+/// time never goes backward. Returns whether the time has been set.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert!(!faketime::set_millis_if_greater(500).expect("set millis"));
+/// assert!(faketime::set_millis_if_greater(2_000).expect("set millis"));
+/// assert_eq!(2_000, faketime::unix_time_as_millis());
+/// ```
+///
+/// The file is still written atomically, but the read and the write together are not: another
+/// writer may update the file in between. It prevents the accidental clock regressions among the
+/// writers, not the races between them.
+///
+/// Returns an error if faketime is neither enabled with a timestamp file path nor kept in memory
+/// via `enable_millis` or `enable_scaled` in current thread, or the timestamp file cannot be read.
+pub fn set_millis_if_greater(millis: u64) -> Result<bool> {
+    with_mode(|mode| {
+        if millis <= mode.millis()? {
+            return Ok(false);
+        }
+        mode.set_time(millis_to_duration(millis))?;
+        Ok(true)
+    })
+}
+
+/// Writes time into the timestamp file of current thread like `set_millis`, or sets the time in
+/// memory keeping the full precision if faketime is enabled via `enable_millis` or
+/// `enable_duration`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_duration(Duration::from_nanos(1_500));
+/// let previous = faketime::set_duration(Duration::new(1, 2)).expect("set duration");
+/// assert_eq!(Duration::from_nanos(1_500), previous);
+/// assert_eq!(Duration::new(1, 2), faketime::unix_time());
+/// ```
+///
+/// Returns the previous time, see `set_millis`. The time is truncated to milliseconds when it is
+/// written to the timestamp file.
+pub fn set_duration(time: Duration) -> Result<Duration> {
+    with_mode(|mode| mode.set_time(time))
+}
+
+/// Advances the time of current thread by `delta` and returns the new time in milliseconds.
+///
+/// It updates the timestamp file like `set_millis`, or the time in memory if faketime is enabled
+/// via `enable_millis`. The `delta` is truncated to milliseconds.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert_eq!(3_000, faketime::advance(Duration::from_secs(2)).expect("advance"));
+/// assert_eq!(3, faketime::unix_time().as_secs());
+/// ```
+///
+/// Returns an error if faketime is neither enabled with a timestamp file path nor kept in memory
+/// via `enable_millis` or `enable_scaled` in current thread, or the timestamp file cannot be read.
+pub fn advance(delta: Duration) -> Result<u64> {
+    let delta = duration_to_millis_checked(delta);
+    with_mode(|mode| {
+        let current = mode.millis()?;
+        let millis = delta
+            .and_then(|delta| current.checked_add(delta))
+            .ok_or_else(|| Error::other("faketime overflow"))?;
+        mode.set_time(millis_to_duration(millis))?;
+        Ok(millis)
+    })
+}
+
+/// Rewinds the time of current thread by `delta` and returns the new time in milliseconds, which
+/// is the inverse of `advance`.
+///
+/// The time saturates at zero rather than underflowing. The `delta` is truncated to
+/// milliseconds.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(3_000);
+/// assert_eq!(1_000, faketime::rewind(Duration::from_secs(2)).expect("rewind"));
+/// assert_eq!(0, faketime::rewind(Duration::from_secs(2)).expect("rewind"));
+/// ```
+///
+/// If faketime is enabled via `enable_monotonic`, the timestamp file is rewound, but the time
+/// returned by `unix_time` holds at the highest time observed until the file catches up.
+///
+/// Returns an error if faketime is neither enabled with a timestamp file path nor kept in memory
+/// via `enable_millis` or `enable_scaled` in current thread, or the timestamp file cannot be read.
+pub fn rewind(delta: Duration) -> Result<u64> {
+    let delta = Millis::from(delta).0;
+    with_mode(|mode| {
+        let millis = mode.millis()?.saturating_sub(delta);
+        mode.set_time(millis_to_duration(millis))?;
+        Ok(millis)
+    })
+}
+
+/// Advances the time in the timestamp file by `delta` and returns the new time in milliseconds,
+/// holding an advisory lock so the concurrent advancers, even in other processes, do not lose
+/// updates.
+///
+/// The lock is taken on the file with `.lock` appended to the path of the timestamp file, because
+/// the timestamp file itself is replaced atomically on write. The lock file is created if missing
+/// and is left in place. The `delta` is truncated to milliseconds.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let tempdir = tempfile::tempdir().expect("create tempdir");
+/// let faketime_file = tempdir.path().join("faketime");
+/// faketime::write_millis(&faketime_file, 1_000).expect("write millis");
+/// match faketime::advance_locked(&faketime_file, Duration::from_secs(2)) {
+///     Ok(millis) => assert_eq!(3_000, millis),
+///     Err(err) => assert_eq!(std::io::ErrorKind::Unsupported, err.kind()),
+/// }
+/// ```
+///
+/// The lock is `flock`, which is available on unix with the cargo feature `libc`, and it only
+/// coordinates the writers which also use `advance_locked`. Otherwise, returns an error of
+/// `ErrorKind::Unsupported` without touching any file, rather than advancing without the lock.
+///
+/// Returns an error if the lock cannot be taken, or the timestamp file cannot be read or written.
+pub fn advance_locked<T: AsRef<Path>>(path: T, delta: Duration) -> Result<u64> {
+    let path = path.as_ref();
+    let _lock = lock_exclusive(path)?;
+    let current = read_millis(path)?;
+    let millis = duration_to_millis_checked(delta)
+        .and_then(|delta| current.checked_add(delta))
+        .ok_or_else(|| Error::other("faketime overflow"))?;
+    write_millis(path, millis)?;
+    Ok(millis)
+}
+
+/// Takes an exclusive `flock` on the lock file of the timestamp file, which is released when the
+/// returned file is closed.
+#[cfg(all(unix, feature = "libc"))]
+fn lock_exclusive(path: &Path) -> Result<File> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    loop {
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(lock);
+        }
+        let err = Error::last_os_error();
+        if err.kind() != ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(not(all(unix, feature = "libc")))]
+fn lock_exclusive(_path: &Path) -> Result<File> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "advance_locked requires the cargo feature libc on unix",
+    ))
+}
+
+/// Advances the time of current thread by 1 millisecond and returns the new time in milliseconds.
+///
+/// The returned values are strictly increasing among all the callers which tick the same file
+/// in turn, which is useful to generate unique timestamps. See `advance`.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(100).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert_eq!(101, faketime::tick_unique().expect("tick"));
+/// assert_eq!(102, faketime::tick_unique().expect("tick"));
+/// assert_eq!(102, faketime::unix_time_as_millis());
+/// ```
+///
+/// Returns an error if faketime is neither enabled with a timestamp file path nor kept in memory
+/// via `enable_millis` or `enable_scaled` in current thread, or the timestamp file cannot be read.
+pub fn tick_unique() -> Result<u64> {
+    advance(Duration::from_millis(1))
+}
+
+/// Writes time into a temporary file and return the file.
+///
+///
+/// It returns the handle to the timestamp file on success.
+///
+/// ```
+/// use std::fs;
+///
+/// let faketime_file = faketime::millis_tempfile(123).expect("create faketime file");
+/// assert_eq!(fs::read_to_string(&faketime_file).ok(), Some("123".to_string()));
+/// ```
+///
+/// The file is deleted when the handle goes out of scope.
+///
+/// ```
+/// let path = {
+///     let faketime_file = faketime::millis_tempfile(123).expect("create faketime file");
+///     faketime_file.to_path_buf()
+/// };
+/// assert!(!path.exists());
+/// ```
+///
+/// The file is created in `std::env::temp_dir()`. If it is unavailable, the returned error names
+/// the directory, and `millis_tempfile_in` can be used to create the file elsewhere.
+pub fn millis_tempfile(millis: u64) -> Result<TempPath> {
+    let path = NamedTempFile::new()
+        .map_err(|err| {
+            Error::new(
+                err.kind(),
+                format!(
+                    "failed to create faketime file in temp dir {}, \
+                     try millis_tempfile_in with another directory: {}",
+                    env::temp_dir().display(),
+                    err
+                ),
+            )
+        })?
+        .into_temp_path();
+    write_millis(&path, millis)?;
+    Ok(path)
+}
+
+/// Writes time into a temporary file in the directory `dir` and return the file.
+///
+/// ```
+/// let tempdir = tempfile::tempdir().expect("create tempdir");
+/// let faketime_file =
+///     faketime::millis_tempfile_in(tempdir.path(), 123).expect("create faketime file");
+/// assert!(faketime_file.starts_with(tempdir.path()));
+/// assert_eq!(123, faketime::validate_file(&faketime_file).expect("read time"));
+/// ```
+///
+/// See `millis_tempfile`.
+pub fn millis_tempfile_in<P: AsRef<Path>>(dir: P, millis: u64) -> Result<TempPath> {
+    let path = NamedTempFile::new_in(dir)?.into_temp_path();
+    write_millis(&path, millis)?;
+    Ok(path)
+}
+
+/// Writes time with the full precision into a temporary file via `write_duration`, and returns
+/// the path of the file like `millis_tempfile`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let time = Duration::new(1, 500_999);
+/// let faketime_file = faketime::duration_tempfile(time).expect("create faketime file");
+/// assert_eq!(time, faketime::read_duration(&faketime_file).expect("read duration"));
+/// ```
+///
+/// The file is read as milliseconds by `enable`, use `enable_duration` to fake the time of current
+/// thread with sub-millisecond precision.
+pub fn duration_tempfile(duration: Duration) -> Result<TempPath> {
+    let path = NamedTempFile::new()?.into_temp_path();
+    write_duration(&path, duration)?;
+    Ok(path)
+}
+
+/// Writes time into a temporary file and returns the file together with the written time.
+///
+/// It is the same as `millis_tempfile`, but also returns `millis` so it can be used again
+/// without repeating the value.
+///
+/// ```
+/// let (faketime_file, millis) =
+///     faketime::millis_tempfile_with(123_456).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert_eq!(millis, faketime::unix_time_as_millis());
+/// ```
+pub fn millis_tempfile_with(millis: u64) -> Result<(TempPath, u64)> {
+    millis_tempfile(millis).map(|path| (path, millis))
+}
+
+/// Writes time into a temporary file, enables faketime in current thread with the file, and
+/// returns a handle which can be cloned to update the file.
+///
+/// It packages the pattern that one component owns the timestamp file and hands out the way to
+/// update it. Any clone can `set` or `advance` the time, and all the threads following the file
+/// observe the update. The file is deleted when the last clone is dropped.
+///
+/// ```
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let clock = faketime::enable_shared_file(1_000).expect("create faketime file");
+/// let reader = clock.clone();
+/// clock.advance(Duration::from_secs(1)).expect("advance");
+/// thread::spawn(move || {
+///     reader.enable();
+///     assert_eq!(2_000, faketime::unix_time_as_millis());
+/// })
+/// .join()
+/// .expect("join thread");
+/// ```
+pub fn enable_shared_file(millis: u64) -> Result<SharedClockFile> {
+    let clock = SharedClockFile {
+        inner: Arc::new(SharedClockInner {
+            path: millis_tempfile(millis)?,
+            lock: Mutex::new(()),
+        }),
+    };
+    clock.enable();
+    Ok(clock)
+}
+
+/// The shared timestamp file created via `enable_shared_file`.
+///
+/// The updates via the clones are serialized, so concurrent `advance` calls do not lose updates.
+#[derive(Debug, Clone)]
+pub struct SharedClockFile {
+    inner: Arc<SharedClockInner>,
+}
+
+#[derive(Debug)]
+struct SharedClockInner {
+    path: TempPath,
+    lock: Mutex<()>,
+}
+
+impl SharedClockFile {
+    /// Gets the path of the timestamp file.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Enables faketime in current thread with the timestamp file.
+    pub fn enable(&self) {
+        enable(self.path());
+    }
+
+    /// Writes time as milliseconds since *UNIX EPOCH* into the timestamp file.
+    pub fn set(&self, millis: u64) -> Result<()> {
+        let _lock = self
+            .inner
+            .lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        write_millis(self.path(), millis)
+    }
+
+    /// Advances the time in the timestamp file by `delta` and returns the new time in
+    /// milliseconds. The `delta` is truncated to milliseconds.
+    pub fn advance(&self, delta: Duration) -> Result<u64> {
+        let _lock = self
+            .inner
+            .lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let current = read_millis(self.path())?;
+        let millis = duration_to_millis_checked(delta)
+            .and_then(|delta| current.checked_add(delta))
+            .ok_or_else(|| Error::other("faketime overflow"))?;
+        write_millis(self.path(), millis)?;
+        Ok(millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_for() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+        let poll = Duration::from_millis(1);
+        let err = wait_for(|now| now.as_secs() >= 2, poll, Duration::from_millis(20))
+            .expect_err("timed out");
+        assert_eq!(ErrorKind::TimedOut, err.kind());
+
+        let path = faketime_file.to_path_buf();
+        let writer = thread::spawn(move || {
+            for millis in (1_000..=10_000).step_by(1_000) {
+                write_millis(&path, millis).expect("write millis");
+                thread::sleep(Duration::from_millis(2));
+            }
+        });
+        let now = wait_for(|now| now.as_secs() >= 5, poll, Duration::from_secs(60))
+            .expect("wait for time");
+        assert!(now >= Duration::from_secs(5));
+        writer.join().expect("join thread");
+    }
+
+    #[test]
+    fn test_set_millis_if_greater() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        enable(&faketime_file);
+        assert!(set_millis_if_greater(1_000).is_err());
+
+        write_millis(&faketime_file, 1_000).expect("write millis");
+        assert!(!set_millis_if_greater(999).expect("set millis"));
+        assert!(!set_millis_if_greater(1_000).expect("set millis"));
+        assert_eq!(Some(1_000), read_millis(&faketime_file).ok());
+        assert!(set_millis_if_greater(1_001).expect("set millis"));
+        assert_eq!(Some(1_001), read_millis(&faketime_file).ok());
+
+        enable_millis(5_000);
+        assert!(!set_millis_if_greater(4_000).expect("set millis"));
+        assert!(set_millis_if_greater(6_000).expect("set millis"));
+        assert_eq!(Duration::from_secs(6), unix_time());
+    }
+
+    #[test]
+    fn test_write_millis_all() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let paths: Vec<_> = (0..3)
+            .map(|i| tempdir.path().join(format!("node{}", i)))
+            .collect();
+        let entries: Vec<_> = paths
+            .iter()
+            .zip([1_000, 2_000, 3_000])
+            .map(|(path, millis)| (path.clone(), millis))
+            .collect();
+        write_millis_all(&entries).expect("write millis");
+        for (path, millis) in &entries {
+            assert_eq!(Some(*millis), read_millis(path).ok());
+        }
+
+        let missing = tempdir.path().join("missing").join("node");
+        let err = write_millis_all(&[
+            (paths[0].as_path(), 4_000),
+            (missing.as_path(), 5_000),
+            (paths[2].as_path(), 6_000),
+        ])
+        .expect_err("missing dir");
+        assert!(err.to_string().contains("after 1 of 3 files were written"));
+        assert_eq!(Some(4_000), read_millis(&paths[0]).ok());
+        assert_eq!(Some(3_000), read_millis(&paths[2]).ok());
+    }
+
+    #[test]
+    fn test_read_back() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let missing = read_back(&faketime_file, 123).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, missing.kind());
+
+        fs::write(&faketime_file, "456").expect("write millis");
+        let mismatch = read_back(&faketime_file, 123).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, mismatch.kind());
+        assert!(read_back(&faketime_file, 456).is_ok());
+    }
+
+    #[test]
+    fn test_from_millis_str() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        for text in [
+            "12345\n",
+            " 12345 ",
+            "x",
+            "",
+            "s:1577836800",
+            "ns:1500999999",
+            "1.5779e12",
+            "2020-01-01",
+            "2020-02-30",
+            "2020-01-01T12:00:00.123+08:00",
+            "123456\n0972d361\n",
+            "123456\n00000000\n",
+        ] {
+            fs::write(&faketime_file, text).expect("write file");
+            assert_eq!(
+                read_millis(&faketime_file).ok().map(millis_to_duration),
+                from_millis_str(text).ok(),
+                "{:?}",
+                text
+            );
+        }
+        assert_eq!(
+            Duration::from_millis(12_345),
+            from_millis_str("12345\n").expect("parse time")
+        );
+    }
+
+    #[test]
+    fn test_mock_file_io() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+
+        assert_eq!(None, read_millis(&faketime_file).ok());
+        fs::write(&faketime_file, "x").expect("write millis");
+        assert_eq!(None, read_millis(&faketime_file).ok());
+        fs::write(&faketime_file, "12345\n").expect("write millis");
+        assert_eq!(12345, read_millis(&faketime_file).expect("read millis"));
+        write_millis(&faketime_file, 54321).expect("write millis");
+        assert_eq!(54321, read_millis(&faketime_file).expect("read millis"));
+    }
+
+    #[test]
+    fn test_tick_unique() {
+        disable();
+        assert!(tick_unique().is_err());
+
+        let faketime_file = millis_tempfile(u64::MAX - 10).expect("create faketime file");
+        enable(&faketime_file);
+        let ticks: Vec<_> = (0..10).map(|_| tick_unique().expect("tick")).collect();
+        assert!(ticks.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Some(&u64::MAX), ticks.last());
+        assert!(tick_unique().is_err());
+    }
+
+    #[test]
+    fn test_enable_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        fs::write(&faketime_file, "123456").expect("write millis");
+
+        enable_file(File::open(&faketime_file).expect("open faketime file"));
+        assert_eq!(123_456, unix_time().as_millis());
+        fs::write(&faketime_file, "1234").expect("write millis");
+        assert_eq!(1_234, unix_time().as_millis());
+        assert!(tick_unique().is_err());
+
+        #[cfg(unix)]
+        {
+            write_millis(&faketime_file, 100).expect("write millis");
+            assert!(unix_time().as_secs() > 1);
+        }
+    }
+
+    #[test]
+    fn test_jitter() {
+        let sample = || -> Vec<u128> { (0..100).map(|_| unix_time().as_millis()).collect() };
+
+        enable_jitter(1_000, 7, 10);
+        let first = sample();
+        assert!(first.iter().all(|millis| (1_000..=1_010).contains(millis)));
+        assert!(first.iter().any(|millis| *millis != first[0]));
+
+        enable_jitter(1_000, 7, 10);
+        assert_eq!(first, sample());
+        enable_jitter(1_000, 8, 10);
+        assert_ne!(first, sample());
+
+        enable_jitter(1_000, 7, 0);
+        assert!(sample().iter().all(|millis| *millis == 1_000));
+        enable_jitter(u64::MAX, 7, u64::MAX);
+        assert!(sample()
+            .iter()
+            .all(|millis| *millis == u128::from(u64::MAX)));
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+
+        let err = validate_file(&faketime_file).expect_err("missing file");
+        assert_eq!(ErrorKind::NotFound, err.kind());
+        assert!(err
+            .to_string()
+            .contains(&faketime_file.display().to_string()));
+
+        fs::write(&faketime_file, "12x").expect("write millis");
+        let err = validate_file(&faketime_file).expect_err("malformed file");
+        assert!(err.to_string().contains("invalid digit"));
+
+        disable();
+        fs::write(&faketime_file, "12345\n").expect("write millis");
+        assert_eq!(12345, validate_file(&faketime_file).expect("valid file"));
+        assert!(set_millis(0).is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+
+        write(&faketime_file, 12345_u64).expect("write millis");
+        assert_eq!(12345, read_millis(&faketime_file).expect("read millis"));
+        write(&faketime_file, Duration::new(12, 345_999_999)).expect("write duration");
+        assert_eq!(12345, read_millis(&faketime_file).expect("read millis"));
+        write(&faketime_file, Duration::MAX).expect("write duration");
+        assert_eq!(u64::MAX, read_millis(&faketime_file).expect("read millis"));
+    }
+
+    #[test]
+    fn test_poll_changes() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+
+        assert_eq!(Some(Duration::from_millis(1_000)), poll_changes());
+        assert_eq!(None, poll_changes());
+        write_millis(&faketime_file, 1_001).expect("write millis");
+        assert_eq!(Some(Duration::from_millis(1_001)), poll_changes());
+        assert_eq!(None, poll_changes());
+        write_millis(&faketime_file, 1_000).expect("write millis");
+        assert_eq!(Some(Duration::from_millis(1_000)), poll_changes());
+    }
+
+    #[test]
+    fn test_monotonic() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+        let start = monotonic();
+        write_millis(&faketime_file, 3_600_000).expect("write millis");
+        assert!(monotonic() - start < Duration::from_secs(60));
+
+        let monotonic_file = millis_tempfile(1_000).expect("create faketime file");
+        enable_monotonic_file(&monotonic_file);
+        assert_eq!(Duration::from_millis(1_000), monotonic());
+        write_millis(&faketime_file, 7_200_000).expect("write millis");
+        assert_eq!(Duration::from_millis(1_000), monotonic());
+        write_millis(&monotonic_file, 2_000).expect("write millis");
+        assert_eq!(Duration::from_millis(2_000), monotonic());
+
+        disable_monotonic_file();
+        assert!(monotonic() - start < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_set_millis_and_advance() {
+        disable();
+        assert!(set_millis(0).is_err());
+        assert!(advance(Duration::from_secs(1)).is_err());
+
+        let faketime_file = millis_tempfile(0).expect("create faketime file");
+        enable(&faketime_file);
+        assert_eq!(0, set_millis(1_000).expect("set millis"));
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        assert_eq!(
+            1_500,
+            advance(Duration::from_micros(500_999)).expect("advance")
+        );
+        assert_eq!(Duration::from_millis(1_500), unix_time());
+        assert!(advance(Duration::MAX).is_err());
+        assert!(advance(Duration::from_millis(u64::MAX)).is_err());
+        assert_eq!(Duration::from_millis(1_500), unix_time());
+    }
+
+    #[test]
+    fn test_set_millis_returns_previous() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        enable(&faketime_file);
+
+        let start = Millis::from(system_unix_time()).0;
+        let previous = set_millis(1_000).expect("set millis");
+        assert!(previous >= start && previous - start < 60_000);
+        assert_eq!(1_000, set_millis(3_000).expect("set millis"));
+        assert_eq!(3_000, set_millis(2_000).expect("set millis"));
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+    }
+
+    #[test]
+    fn test_enable_duration() {
+        enable_duration(Duration::new(1, 123_456_789));
+        assert_eq!(123_456_789, unix_time().subsec_nanos());
+        assert_eq!(
+            Duration::new(1, 123_456_789),
+            set_duration(Duration::new(2, 1)).expect("set duration")
+        );
+        assert_eq!(1, unix_time().subsec_nanos());
+        assert_eq!(2_000, set_millis(3_000).expect("set millis"));
+
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+        assert_eq!(
+            Duration::from_secs(1),
+            set_duration(Duration::new(2, 999_999)).expect("set duration")
+        );
+        assert_eq!(Duration::from_secs(2), unix_time());
+    }
+
+    #[test]
+    fn test_enable_system_time() {
+        let now = SystemTime::now();
+        enable_system_time(now).expect("after UNIX EPOCH");
+        let expected = now.duration_since(SystemTime::UNIX_EPOCH).expect("after");
+        let faked = unix_time();
+        assert!(faked.max(expected) - faked.min(expected) < Duration::from_millis(1));
+
+        let before_epoch = SystemTime::UNIX_EPOCH - Duration::from_secs(1);
+        let err = enable_system_time(before_epoch).expect_err("before UNIX EPOCH");
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+        assert_eq!(faked, unix_time());
+    }
+
+    #[test]
+    fn test_enable_with_now() {
+        let before = system_unix_time();
+        enable_with_now().expect("freeze now");
+        let after = system_unix_time();
+
+        let now = unix_time();
+        thread::sleep(Duration::from_millis(2));
+        assert_eq!(now, unix_time());
+        assert_eq!(0, now.subsec_nanos() % 1_000_000);
+        assert!(before.saturating_sub(Duration::from_millis(1)) <= now && now <= after);
+    }
+
+    #[test]
+    fn test_enable_millis() {
+        enable_millis(1_000);
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        assert_eq!(1_000, set_millis(2_000).expect("set millis"));
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+        assert_eq!(2_001, tick_unique().expect("tick"));
+        assert_eq!(Duration::from_millis(2_001), unix_time());
+    }
+
+    #[test]
+    fn test_enable_offset() {
+        let day = Duration::from_secs(86_400);
+        let tolerance = Duration::from_secs(60);
+
+        enable_offset(86_400_000);
+        let start = system_unix_time();
+        let shifted = unix_time();
+        assert!(shifted - start - day < tolerance);
+        thread::sleep(Duration::from_millis(20));
+        assert!(unix_time() - shifted >= Duration::from_millis(20));
+
+        enable_offset(-86_400_000);
+        let start = system_unix_time();
+        let shifted = unix_time();
+        assert!(shifted + day - start < tolerance);
+        thread::sleep(Duration::from_millis(20));
+        assert!(unix_time() - shifted >= Duration::from_millis(20));
+
+        enable_offset(i64::MIN);
+        assert_eq!(Duration::from_secs(0), unix_time());
+    }
+
+    #[test]
+    fn test_enable_replay() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let log_file = tempdir.path().join("log");
+        let replay = |count| -> Vec<u128> { (0..count).map(|_| unix_time().as_millis()).collect() };
+
+        assert!(enable_replay(&log_file, ReplayEnd::Hold).is_err());
+        fs::write(&log_file, "\n").expect("write log");
+        assert!(enable_replay(&log_file, ReplayEnd::Hold).is_err());
+        fs::write(&log_file, "1\nx\n").expect("write log");
+        assert!(enable_replay(&log_file, ReplayEnd::Hold).is_err());
+
+        fs::write(&log_file, "100\n 200 \n\n2020-01-01\n").expect("write log");
+        enable_replay(&log_file, ReplayEnd::Hold).expect("enable replay");
+        assert_eq!(
+            vec![100, 200, 1_577_836_800_000, 1_577_836_800_000],
+            replay(4)
+        );
+        enable_replay(&log_file, ReplayEnd::Loop).expect("enable replay");
+        assert_eq!(vec![100, 200, 1_577_836_800_000, 100, 200], replay(5));
+    }
+
+    #[test]
+    fn test_command_env_keeps_replay_position() {
+        enable_cycle(vec![1_000, 2_000]);
+        assert_eq!(Some(OsString::from("1000")), exported_millis());
+        assert_eq!(Duration::from_secs(1), unix_time());
+        assert_eq!(Some(OsString::from("2000")), exported_millis());
+        assert_eq!(1, cycle_position());
+
+        enable_jitter(1_000, 7, 100);
+        let first = unix_time();
+        enable_jitter(1_000, 7, 100);
+        assert_eq!(Some(OsString::from("1000")), exported_millis());
+        assert_eq!(first, unix_time());
+    }
+
+    #[test]
+    fn test_reset() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        reset();
+        FAKETIME_MODE.with(|mode_cell| assert!(matches!(*mode_cell.borrow(), Mode::Undecided)));
+    }
+
+    #[test]
+    fn test_set_clock_closure() {
+        let counter = Arc::new(AtomicU64::new(1_000));
+        let clock_counter = Arc::clone(&counter);
+        set_clock_closure(Box::new(move || {
+            Duration::from_millis(clock_counter.fetch_add(1, Ordering::SeqCst))
+        }));
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        assert_eq!(Duration::from_millis(1_001), unix_time());
+        counter.store(5_000, Ordering::SeqCst);
+        assert_eq!(Duration::from_millis(5_000), unix_time());
+        assert_eq!("enabled with a closure", describe());
+        assert!(set_millis(0).is_err());
+
+        enable_millis(123);
+        assert_eq!(Duration::from_millis(123), unix_time());
+        assert_eq!(5_001, counter.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_enable_named_clock() {
+        let clock_a = Arc::new(crate::clock::FakeClock::new(1_000));
+        ClockRegistry::global().register("test-named-a", Arc::clone(&clock_a) as _);
+        let clock_b = Arc::new(crate::clock::FakeClock::new(2_000));
+        ClockRegistry::global().register("test-named-b", clock_b);
+
+        enable_named_clock("test-named-a");
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        assert_eq!("enabled with clock test-named-a", describe());
+        clock_a.set_millis(1_500);
+        assert_eq!(Duration::from_millis(1_500), unix_time());
+        assert!(set_millis(0).is_err());
+
+        let snapshot = snapshot();
+        enable_named_clock("test-named-b");
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+        restore(&snapshot);
+        assert_eq!(Duration::from_millis(1_500), unix_time());
+
+        enable_named_clock("test-named-missing");
+        let start = system_unix_time();
+        assert!(unix_time() - start < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_enable_from_args() {
+        enable_millis(1);
+        assert!(!enable_from_args(&["app", "--verbose"]).expect("no flags"));
+        assert!(!enable_from_args::<String>(&[]).expect("no args"));
+        assert_eq!("frozen at 1ms", describe());
+
+        let faketime_file = millis_tempfile(123_456).expect("create faketime file");
+        let path = faketime_file.to_str().expect("utf-8 path").to_string();
+        assert!(enable_from_args(&[
+            "app".to_string(),
+            "--faketime-file".to_string(),
+            path.clone()
+        ])
+        .expect("file flag"));
+        assert_eq!(Duration::from_millis(123_456), unix_time());
+        assert_eq!(format!("enabled with file {}", path), describe());
+
+        assert!(enable_from_args(&["--faketime-millis", "1000"]).expect("millis flag"));
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        assert!(enable_from_args(&[format!("--faketime-file={}", path)]).expect("inline file"));
+        assert_eq!(Duration::from_millis(123_456), unix_time());
+        assert!(enable_from_args(&["--faketime-millis=2000"]).expect("inline millis"));
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+        let last = ["--faketime-millis=3000", "--faketime-millis", "4000"];
+        assert!(enable_from_args(&last).expect("last wins"));
+        assert_eq!(Duration::from_millis(4_000), unix_time());
+
+        for invalid in [
+            &["--faketime-millis"][..],
+            &["--faketime-millis", "abc"][..],
+        ] {
+            let err = enable_from_args(invalid).expect_err("invalid flags");
+            assert_eq!(ErrorKind::InvalidInput, err.kind());
+        }
+        assert!(enable_from_args(&["--faketime-file"]).is_err());
+        assert_eq!(Duration::from_millis(4_000), unix_time());
+    }
+
+    #[test]
+    fn test_enable_quantized() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable_quantized(&faketime_file, Duration::from_millis(10));
+        for (millis, expected) in [
+            (1_000, 1_000),
+            (1_001, 1_000),
+            (1_009, 1_000),
+            (1_010, 1_010),
+        ] {
+            write_millis(&faketime_file, millis).expect("write millis");
+            assert_eq!(expected, crate::unix_time_as_millis());
+        }
+        assert_eq!(1_015, advance(Duration::from_millis(5)).expect("advance"));
+        assert_eq!(1_010, crate::unix_time_as_millis());
+        assert_eq!(1_015, read_millis(&faketime_file).expect("read millis"));
+
+        let snapshot = snapshot();
+        disable();
+        restore(&snapshot);
+        assert_eq!(1_010, crate::unix_time_as_millis());
+
+        assert_eq!(
+            Duration::from_millis(20),
+            quantize(Duration::new(0, 29_999_999), Duration::from_millis(10))
+        );
+        assert_eq!(
+            Duration::from_secs(3),
+            quantize(Duration::new(4, 999), Duration::from_secs(3))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "quantum must not be zero")]
+    fn test_enable_quantized_zero() {
+        enable_quantized("faketime", Duration::from_secs(0));
+    }
+
+    #[cfg(not(all(unix, feature = "libc")))]
+    #[test]
+    fn test_advance_locked_unsupported() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        let err =
+            advance_locked(&faketime_file, Duration::from_millis(2)).expect_err("unsupported");
+        assert_eq!(ErrorKind::Unsupported, err.kind());
+        assert_eq!(1_000, read_millis(&faketime_file).expect("read millis"));
+    }
+
+    #[cfg(all(unix, feature = "libc"))]
+    #[test]
+    fn test_advance_locked() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        let advancers: Vec<_> = (0..8)
+            .map(|_| {
+                let path = faketime_file.to_path_buf();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        advance_locked(&path, Duration::from_millis(2)).expect("advance locked");
+                    }
+                })
+            })
+            .collect();
+        for advancer in advancers {
+            advancer.join().expect("join thread");
+        }
+        assert_eq!(1_800, read_millis(&faketime_file).expect("read millis"));
+
+        let err = advance_locked(&faketime_file, Duration::MAX).expect_err("overflow");
+        assert_eq!("faketime overflow", err.to_string());
+        let mut lock_path = faketime_file.as_os_str().to_owned();
+        lock_path.push(".lock");
+        fs::remove_file(lock_path).expect("remove lock file");
+    }
+
+    #[test]
+    fn test_enable_shared_file() {
+        let clock = enable_shared_file(1_000).expect("create faketime file");
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        let path = clock.path().to_path_buf();
+        let advancers: Vec<_> = (0..4)
+            .map(|_| {
+                let clock = clock.clone();
+                thread::spawn(move || {
+                    clock.enable();
+                    for _ in 0..25 {
+                        clock.advance(Duration::from_millis(10)).expect("advance");
+                    }
+                    unix_time()
+                })
+            })
+            .collect();
+        for advancer in advancers {
+            assert!(advancer.join().expect("join thread") > Duration::from_millis(1_000));
+        }
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+        clock.set(3_000).expect("set");
+        assert_eq!(Duration::from_millis(3_000), unix_time());
+
+        let last = clock.clone();
+        drop(clock);
+        assert!(path.exists());
+        drop(last);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_sanity_check() {
+        let faketime_file = millis_tempfile(1_577_880_000_000).expect("create faketime file");
+        sanity_check(&faketime_file).expect("sane value");
+        write_millis(&faketime_file, SANE_MAX_MILLIS).expect("write millis");
+        sanity_check(&faketime_file).expect("year 3000");
+        enable_millis(123);
+
+        write_millis(&faketime_file, 0).expect("write millis");
+        let err = sanity_check(&faketime_file).expect_err("zero");
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+        assert!(err.to_string().ends_with("0ms is zero"));
+
+        write_millis(&faketime_file, 1_577_880_000_000_000_000).expect("write millis");
+        let err = sanity_check(&faketime_file).expect_err("far future");
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+        assert!(err.to_string().ends_with("is after the year 3000"));
+
+        assert!(sanity_check("/path/to/missing/faketime").is_err());
+        assert_eq!("frozen at 123ms", describe());
+    }
+
+    #[test]
+    fn test_duration_tempfile() {
+        let time = Duration::new(1_577_880_000, 123_456_789);
+        let faketime_file = duration_tempfile(time).expect("create faketime file");
+        assert_eq!(time, read_duration(&faketime_file).expect("read duration"));
+        assert_eq!(
+            1_577_880_000_123,
+            read_millis(&faketime_file).expect("read millis")
+        );
+
+        write_millis_checksummed(&faketime_file, 1_500).expect("write millis");
+        assert_eq!(
+            Duration::from_millis(1_500),
+            read_duration(&faketime_file).expect("read duration")
+        );
+        fs::write(&faketime_file, "ns:abc").expect("write file");
+        assert!(read_duration(&faketime_file).is_err());
+    }
+
+    #[test]
+    fn test_call_count() {
+        reset_call_count();
+        assert_eq!(0, call_count());
+        enable_millis(1_000);
+        for _ in 0..10 {
+            unix_time();
+        }
+        crate::unix_time_as_millis();
+        assert_eq!(11, call_count());
+        thread::spawn(|| assert_eq!(0, call_count()))
+            .join()
+            .expect("join thread");
+        reset_call_count();
+        assert_eq!(0, call_count());
+    }
+
+    #[test]
+    fn test_rewind() {
+        let faketime_file = millis_tempfile(5_000).expect("create faketime file");
+        enable(&faketime_file);
+        assert_eq!(3_000, rewind(Duration::from_secs(2)).expect("rewind"));
+        assert_eq!(Duration::from_secs(3), unix_time());
+        assert_eq!(0, rewind(Duration::MAX).expect("rewind past zero"));
+        assert_eq!(Duration::from_secs(0), unix_time());
+
+        write_millis(&faketime_file, 5_000).expect("write millis");
+        enable_monotonic(&faketime_file);
+        assert_eq!(Duration::from_secs(5), unix_time());
+        assert_eq!(
+            4_000,
+            rewind(Duration::from_secs(1)).expect("rewind monotonic")
+        );
+        assert_eq!(Duration::from_secs(5), unix_time());
+        assert_eq!(4_000, read_millis(&faketime_file).expect("read millis"));
+
+        disable();
+        assert!(rewind(Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_system_elapsed_since_enable() {
+        disable();
+        assert_eq!(None, system_elapsed_since_enable());
+        enable_scaled(1_000, 10.0);
+        let start = unix_time();
+        thread::sleep(Duration::from_millis(50));
+        let real = system_elapsed_since_enable().expect("enabled");
+        let faked = unix_time() - start;
+        assert!(real >= Duration::from_millis(50));
+        let ratio = faked.as_secs_f64() / real.as_secs_f64();
+        assert!((5.0..=10.5).contains(&ratio), "ratio {}", ratio);
+
+        with_frozen(1, || {
+            assert!(system_elapsed_since_enable().expect("frozen") < real)
+        });
+        assert!(system_elapsed_since_enable().expect("restored") >= real);
+    }
+
+    #[test]
+    fn test_enable_tail() {
+        let faketime_file = millis_tempfile(0).expect("create faketime file");
+        fs::write(&faketime_file, "1000\n2000\n3000\n").expect("write file");
+        enable_tail(&faketime_file);
+        assert_eq!(Duration::from_millis(3_000), unix_time());
+        fs::write(&faketime_file, "1000 2000\n\ts:4 \n").expect("write file");
+        assert_eq!(Duration::from_millis(4_000), unix_time());
+        assert_eq!(5_000, advance(Duration::from_secs(1)).expect("advance"));
+        assert_eq!(Duration::from_millis(5_000), unix_time());
+
+        fs::write(&faketime_file, " \n").expect("write file");
+        let start = system_unix_time();
+        assert!(unix_time() - start < Duration::from_secs(60));
+
+        enable(&faketime_file);
+        fs::write(&faketime_file, "1000\n2000\n").expect("write file");
+        assert!(read_millis(&faketime_file).is_err());
+    }
+
+    #[test]
+    fn test_enable_from_file_or_default() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable_from_file_or_default(&faketime_file, 2_000).expect("existing file");
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        assert_eq!(1_000, read_millis(&faketime_file).expect("read millis"));
+
+        fs::write(&faketime_file, "x").expect("write file");
+        enable_from_file_or_default(&faketime_file, 2_000).expect("invalid file");
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let missing = tempdir.path().join("faketime");
+        enable_from_file_or_default(&missing, 3_000).expect("missing file");
+        assert_eq!(Duration::from_millis(3_000), unix_time());
+        assert_eq!(3_000, read_millis(&missing).expect("read millis"));
+
+        let unwritable = tempdir.path().join("missing").join("faketime");
+        assert!(enable_from_file_or_default(unwritable, 4_000).is_err());
+        assert_eq!(
+            format!("enabled with file {}", missing.display()),
+            describe()
+        );
+    }
+
+    /// Gets `FAKETIME_MILLIS` which `command_env` exports for current thread.
+    fn exported_millis() -> Option<OsString> {
+        let mut cmd = Command::new("date");
+        command_env(&mut cmd);
+        cmd.get_envs()
+            .find(|(key, _)| *key == KEY_FAKETIME_MILLIS)
+            .and_then(|(_, value)| value.map(OsStr::to_os_string))
+    }
+
+    #[test]
+    fn test_named_clock_calling_faketime() {
+        struct Describing;
+
+        impl Clock for Describing {
+            fn unix_time(&self) -> Duration {
+                assert_eq!("enabled with clock test-named-describing", describe());
+                Duration::from_secs(1)
+            }
+        }
+
+        ClockRegistry::global().register("test-named-describing", Arc::new(Describing));
+        enable_named_clock("test-named-describing");
+        assert_eq!(Duration::from_secs(1), unix_time());
+        assert_eq!(None, enabled_since());
+
+        assert_eq!(Some(OsString::from("1000")), exported_millis());
+        assert_eq!(Duration::from_secs(1), pause());
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        assert!(resume().is_err());
+
+        enable_scaled(1_000_000, 1_000.0);
+        let paused = pause();
+        assert_eq!("paused at", &describe()[..9]);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(paused, unix_time());
+        assert_eq!(paused, pause());
+        resume().expect("resume");
+        let resumed = unix_time();
+        assert!(resumed >= paused);
+        assert!(resumed - paused < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(10));
+        assert!(unix_time() - resumed >= Duration::from_secs(9));
+        assert_eq!("scaled by 1000", &describe()[..14]);
+
+        enable_millis(1_000);
+        pause();
+        advance(Duration::from_secs(1)).expect("advance");
+        resume().expect("resume");
+        assert_eq!("frozen at 2000ms", describe());
+
+        enable_cycle(vec![1_000, 2_000, 3_000]);
+        assert_eq!(Duration::from_secs(1), unix_time());
+        assert_eq!(Duration::from_secs(2), pause());
+        assert_eq!(Duration::from_secs(2), unix_time());
+        enable_jitter(1_000, 7, 100);
+        assert_eq!(Duration::from_secs(1), pause());
+
+        enable_offset(-3_600_000);
+        let paused = pause();
+        thread::sleep(Duration::from_millis(20));
+        resume().expect("resume");
+        let resumed = unix_time();
+        assert!(resumed >= paused && resumed - paused < Duration::from_secs(10));
+        assert!(system_unix_time() - resumed > Duration::from_secs(3_500));
+        assert!(resume().is_err());
+    }
+
+    #[test]
+    fn test_enable_cycle() {
+        enable_cycle(vec![1_000, 2_000]);
+        assert_eq!(0, cycle_position());
+        let cycled: Vec<_> = (0..5).map(|_| Millis::from(unix_time()).0).collect();
+        assert_eq!(vec![1_000, 2_000, 1_000, 2_000, 1_000], cycled);
+        assert_eq!(1, cycle_position());
+        assert_eq!(Duration::from_secs(2), unix_time());
+        assert_eq!(0, cycle_position());
+
+        enable_millis(1_000);
+        assert_eq!(0, cycle_position());
+        assert!(std::panic::catch_unwind(|| enable_cycle(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_with_frozen() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+
+        let result = std::panic::catch_unwind(|| {
+            with_frozen(2_000, || {
+                assert_eq!(Duration::from_millis(2_000), unix_time());
+                with_frozen(3_000, || {
+                    assert_eq!(Duration::from_millis(3_000), unix_time())
+                });
+                assert_eq!(Duration::from_millis(2_000), unix_time());
+                panic!("restore on panic");
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        set_millis(1_500).expect("set millis");
+        assert_eq!(Some(1_500), read_millis(&faketime_file).ok());
+    }
+
+    #[test]
+    fn test_age_of_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        assert_eq!(
+            ErrorKind::NotFound,
+            age_of_file(&faketime_file).unwrap_err().kind()
+        );
+
+        enable_millis(0);
+        write_millis(&faketime_file, 1_000).expect("write millis");
+        let minute = Duration::from_secs(60);
+        assert!(age_of_file(&faketime_file).expect("file age") < minute);
+
+        let hour = Duration::from_secs(3_600);
+        let file = File::options()
+            .write(true)
+            .open(&faketime_file)
+            .expect("open file");
+        file.set_modified(std::time::SystemTime::now() - hour)
+            .expect("set modified time");
+        let age = age_of_file(&faketime_file).expect("file age");
+        assert!(hour <= age && age < hour + minute);
+
+        file.set_modified(std::time::SystemTime::now() + hour)
+            .expect("set modified time");
+        assert_eq!(
+            Duration::ZERO,
+            age_of_file(&faketime_file).expect("file age")
+        );
+    }
+
+    #[test]
+    fn test_enable_uncached() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        // Rewrites the content in place and keeps the modification time, like a virtual file.
+        let rewrite = |millis: u64| {
+            let mut file = File::create(&faketime_file).expect("create file");
+            write!(file, "{}", millis).expect("write millis");
+            file.set_modified(modified).expect("set modified time");
+        };
+
+        rewrite(1_000);
+        enable_uncached(&faketime_file);
+        for millis in [1_000, 2_000, 1_500, 3_000] {
+            rewrite(millis);
+            assert_eq!(
+                modified,
+                fs::metadata(&faketime_file)
+                    .and_then(|metadata| metadata.modified())
+                    .expect("modified time")
+            );
+            assert_eq!(Duration::from_millis(millis), unix_time());
+        }
+    }
+
+    #[test]
+    fn test_enable_once() {
+        let first = millis_tempfile(1_000).expect("create faketime file");
+        let second = millis_tempfile(2_000).expect("create faketime file");
+        assert!(enable_once(&first));
+        assert!(!enable_once(&second));
+        assert_eq!(Duration::from_secs(1), unix_time());
+
+        disable();
+        assert!(!enable_once(&second));
+        assert_eq!("disabled", describe());
+    }
+
+    #[test]
+    fn test_enable_fresh() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let hour = Duration::from_secs(3_600);
+        assert!(enable_fresh(&faketime_file, hour).is_err());
+
+        fs::write(&faketime_file, "1000").expect("write millis");
+        File::options()
+            .write(true)
+            .open(&faketime_file)
+            .and_then(|file| file.set_modified(std::time::SystemTime::now() - hour * 2))
+            .expect("set modified time");
+        disable();
+        let err = enable_fresh(&faketime_file, hour).expect_err("stale file");
+        assert!(err.to_string().contains("stale"));
+        assert!(unix_time() > Duration::from_millis(1_000));
+
+        enable_fresh(&faketime_file, hour * 3).expect("fresh file");
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+    }
+
+    #[test]
+    fn test_enable_readonly() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        assert!(enable_readonly(tempdir.path().join("missing")).is_err());
+
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable_readonly(&faketime_file).expect("open faketime file");
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+
+        let err = advance(Duration::from_secs(1)).expect_err("advance read-only");
+        assert_eq!(ErrorKind::PermissionDenied, err.kind());
+        assert!(set_millis(0).is_err());
+        assert!(tick_unique().is_err());
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+    }
+
+    #[test]
+    fn test_writer() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let mut writer = Writer::new(&faketime_file).expect("create writer");
+        writer.set(0).expect("write millis");
+
+        let reader_file = faketime_file.clone();
+        let reader = thread::spawn(move || {
+            let mut last = 0;
+            while last < 1_000 {
+                let millis = read_millis(&reader_file).expect("read millis");
+                assert!(millis >= last);
+                last = millis;
+            }
+        });
+        for millis in 1..=1_000 {
+            writer.set(millis).expect("write millis");
+        }
+        reader.join().expect("join reader");
+
+        drop(writer);
+        let files: Vec<_> = fs::read_dir(tempdir.path())
+            .expect("read dir")
+            .map(|entry| entry.expect("read entry").path())
+            .collect();
+        assert_eq!(vec![faketime_file], files);
+    }
+
+    #[test]
+    fn test_writer_temp_path_taken() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let mut writer = Writer::new(&faketime_file).expect("create writer");
+        writer.set(1).expect("write millis");
+        let taken = writer.temp_path.clone();
+        fs::write(&taken, "other").expect("take temp path");
+
+        writer.set(2).expect("write millis");
+        assert_ne!(taken, writer.temp_path);
+        assert_eq!("other", fs::read_to_string(&taken).expect("read other"));
+        assert_eq!(2, read_millis(&faketime_file).expect("read millis"));
+        drop(writer);
+        assert!(taken.exists());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(0, parse_millis("1970-01-01").expect("parse date"));
+        assert_eq!(86_400_000, parse_millis("1970-01-02").expect("parse date"));
+        assert_eq!(
+            946_684_800_000,
+            parse_millis("2000-01-01").expect("parse date")
+        );
+        assert_eq!(
+            1_582_934_400_000,
+            parse_millis("2020-02-29").expect("parse date")
+        );
+        assert_eq!(
+            1_583_020_800_000,
+            parse_millis("2020-03-01").expect("parse date")
+        );
+
+        assert!(parse_millis("2019-02-29").is_err());
+        assert!(parse_millis("2020-13-01").is_err());
+        assert!(parse_millis("2020-1-01").is_err());
+        assert!(parse_millis("1969-12-31").is_err());
+        assert!(parse_millis("2020-01-01-01").is_err());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0, crc32(b""));
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn test_read_checksummed_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        write_millis_checksummed(&faketime_file, 123_456).expect("write millis");
+        assert_eq!(123_456, read_millis(&faketime_file).expect("read millis"));
+
+        let checksum = format!("{:08X}", crc32(b"2020-01-01"));
+        fs::write(&faketime_file, format!("2020-01-01\r\n{}\r\n", checksum)).expect("write");
+        assert_eq!(
+            1_577_836_800_000,
+            read_millis(&faketime_file).expect("read date")
+        );
+
+        fs::write(&faketime_file, "123457\n0972d361\n").expect("write torn file");
+        let err = read_millis(&faketime_file).expect_err("checksum mismatch");
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+        fs::write(&faketime_file, "123456\nnot hex\n").expect("write invalid checksum");
+        assert!(read_millis(&faketime_file).is_err());
+
+        enable(&faketime_file);
+        let start = system_unix_time();
+        assert!(unix_time() - start < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(
+            1_577_836_800_000,
+            parse_millis("s:1577836800").expect("parse seconds")
+        );
+        assert_eq!(1_500, parse_millis("s:1.5").expect("parse seconds"));
+        assert_eq!(0, parse_millis("s: 0").expect("parse seconds"));
+
+        assert!(parse_millis("s:").is_err());
+        assert!(parse_millis("s:-1").is_err());
+        assert!(parse_millis("s:2020-01-01").is_err());
+        assert!(parse_millis(&format!("s:{}", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_parse_exact_seconds() {
+        assert_eq!(
+            1_577_836_800_123,
+            parse_millis("s:1577836800.123").expect("parse seconds")
+        );
+        assert_eq!(1_500, parse_millis("s:1.5").expect("parse seconds"));
+        assert_eq!(1_001, parse_millis("s:1.0019").expect("parse seconds"));
+        assert_eq!(
+            u64::MAX,
+            parse_millis(&format!("s:{}.{}", u64::MAX / 1_000, u64::MAX % 1_000))
+                .expect("parse seconds")
+        );
+        assert!(parse_millis("s:1.-5").is_err());
+    }
+
+    #[test]
+    fn test_parse_nanos() {
+        assert_eq!(1_500, parse_millis("ns:1500999999").expect("parse nanos"));
+        assert_eq!(0, parse_millis("ns: 0").expect("parse nanos"));
+        assert_eq!(
+            u64::MAX,
+            parse_millis(&format!("ns:{}", u128::from(u64::MAX) * 1_000_000)).expect("parse nanos")
+        );
+
+        assert!(parse_millis("ns:").is_err());
+        assert!(parse_millis("ns:1.5").is_err());
+        assert!(parse_millis(&format!("ns:{}", u128::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_write_format() {
+        for millis in [
+            0,
+            1,
+            999,
+            1_000,
+            1_577_880_000_500,
+            253_402_300_799_999,
+            u64::MAX,
+        ] {
+            for format in [
+                WriteFormat::Millis,
+                WriteFormat::Seconds,
+                WriteFormat::Rfc3339,
+                WriteFormat::NanosPrefixed,
+            ] {
+                let text = format.format(millis);
+                assert_eq!(
+                    millis,
+                    parse_millis(&text).expect("parse format"),
+                    "{}",
+                    text
+                );
+            }
+        }
+        assert_eq!(
+            "s:1577880000.5",
+            WriteFormat::Seconds.format(1_577_880_000_500)
+        );
+        assert_eq!("s:1", WriteFormat::Seconds.format(1_000));
+        assert_eq!("1970-01-01T00:00:00Z", WriteFormat::Rfc3339.format(0));
+        assert_eq!(
+            "9999-12-31T23:59:59.999Z",
+            WriteFormat::Rfc3339.format(253_402_300_799_999)
+        );
+        assert_eq!(
+            "253402300800000",
+            WriteFormat::Rfc3339.format(253_402_300_800_000)
+        );
+        assert_eq!("ns:1000000", WriteFormat::NanosPrefixed.format(1));
+        assert_eq!(
+            Some(WriteFormat::NanosPrefixed),
+            WriteFormat::from_env_value("nanos")
+        );
+        assert_eq!(None, WriteFormat::from_env_value("iso"));
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        for days in [-719_468, -1, 0, 59, 365, 10_957, 18_262, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days, days_from_civil(year, month, day));
+        }
+        assert_eq!((2020, 2, 29), civil_from_days(18_321));
+    }
+
+    #[test]
+    fn test_read_seconds_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        fs::write(&faketime_file, "s:1577836800\n").expect("write seconds");
+        enable(&faketime_file);
+        assert_eq!(Duration::from_secs(1_577_836_800), unix_time());
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(
+            1_577_900_000_000,
+            parse_millis("1.5779e12").expect("parse float")
+        );
+        assert_eq!(
+            1_577_836_800_000,
+            parse_millis("1577836800000.0").expect("parse float")
+        );
+        assert_eq!(2, parse_millis("1.5").expect("parse float"));
+        assert_eq!(1, parse_millis("1.4999").expect("parse float"));
+
+        assert!(parse_millis("NaN").is_err());
+        assert!(parse_millis("inf").is_err());
+        assert!(parse_millis("-1.0").is_err());
+        assert!(parse_millis("1e20").is_err());
+    }
+
+    #[test]
+    fn test_enable_monotonic() {
+        let faketime_file = millis_tempfile(2_000).expect("create faketime file");
+        enable_monotonic(&faketime_file);
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+        assert_eq!(2_000, set_millis(1_000).expect("set millis"));
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+        write_millis(&faketime_file, 2_500).expect("write millis");
+        assert_eq!(Duration::from_millis(2_500), unix_time());
+
+        enable(&faketime_file);
+        write_millis(&faketime_file, 1_000).expect("write millis");
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+    }
+
+    #[test]
+    fn test_scope_with_faketime() {
+        disable();
+        let mut times = [0; 4];
+        scope_with_faketime(123_456, |s| {
+            assert_eq!(Duration::from_millis(123_456), unix_time());
+            for time in times.iter_mut() {
+                s.spawn(move || *time = Millis::from(unix_time()).0);
+            }
+            let plain = s.scope().spawn(|| Millis::from(unix_time()).0);
+            assert_ne!(123_456, plain.join().expect("join thread"));
+        });
+        assert_eq!([123_456; 4], times);
+        assert_eq!("disabled", describe());
+    }
+
+    #[test]
+    fn test_with_scaled() {
+        enable_millis(123_456);
+        let (real, scaled) = with_scaled(1_000_000, 50.0, || {
+            let start = system_monotonic();
+            let scaled_start = unix_time();
+            thread::sleep(Duration::from_millis(20));
+            let scaled = unix_time() - scaled_start;
+            (system_monotonic() - start, scaled)
+        });
+        assert!(scaled >= Duration::from_millis(1_000 - 1), "{:?}", scaled);
+        assert!(
+            scaled <= real * 50 + Duration::from_millis(1),
+            "{:?}",
+            scaled
+        );
+        assert_eq!(Duration::from_millis(123_456), unix_time());
+
+        assert!(std::panic::catch_unwind(|| with_scaled(0, 2.0, || panic!("scaled"))).is_err());
+        assert_eq!(Duration::from_millis(123_456), unix_time());
+    }
+
+    #[test]
+    fn test_advance_scaled() {
+        enable_scaled(10_000, 50.0);
+        let start = crate::unix_time_as_millis();
+        let jumped = advance(Duration::from_secs(1)).expect("advance");
+        assert!(jumped >= start + 1_000);
+        assert!(jumped - start < 1_000 + 500, "{} {}", jumped, start);
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(crate::unix_time_as_millis() >= jumped + 1_000 - 1);
+
+        assert!(set_millis(5_000).expect("set millis") >= jumped + 1_000 - 1);
+        let now = crate::unix_time_as_millis();
+        assert!((5_000..5_500).contains(&now), "{}", now);
+    }
+
+    #[test]
+    #[should_panic(expected = "non-negative")]
+    fn test_scaled_negative_factor() {
+        enable_scaled(0, -1.0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_enable_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        enable_writable(&faketime_file).expect("writable");
+        assert_eq!(faketime_file, current_path().expect("path"));
+        assert_eq!(0, fs::read_dir(tempdir.path()).expect("read dir").count());
+
+        disable();
+        let missing = tempdir.path().join("missing").join("faketime");
+        let message = enable_writable(&missing)
+            .expect_err("missing dir")
+            .to_string();
+        assert!(message.contains("missing"), "{}", message);
+        assert_eq!("disabled", describe());
+
+        let readonly_dir = tempdir.path().join("readonly");
+        fs::create_dir(&readonly_dir).expect("create dir");
+        fs::set_permissions(&readonly_dir, fs::Permissions::from_mode(0o555))
+            .expect("set permissions");
+        // Root bypasses the permission check.
+        if fs::write(readonly_dir.join("probe"), "").is_err() {
+            let message = enable_writable(readonly_dir.join("faketime"))
+                .expect_err("read-only dir")
+                .to_string();
+            assert!(message.contains("readonly"), "{}", message);
+        }
+        fs::set_permissions(&readonly_dir, fs::Permissions::from_mode(0o755))
+            .expect("set permissions");
+    }
+
+    #[test]
+    fn test_enabled_since() {
+        disable();
+        assert_eq!(None, enabled_since());
+
+        let faketime_file = millis_tempfile(10_000).expect("create faketime file");
+        enable(&faketime_file);
+        assert_eq!(Some(Duration::from_secs(0)), enabled_since());
+        write_millis(&faketime_file, 12_500).expect("write millis");
+        assert_eq!(Some(Duration::from_millis(2_500)), enabled_since());
+        write_millis(&faketime_file, 9_000).expect("write millis");
+        assert_eq!(Some(Duration::from_secs(0)), enabled_since());
+
+        write_millis(&faketime_file, 12_000).expect("write millis");
+        with_frozen(100, || {
+            assert_eq!(Some(Duration::from_secs(0)), enabled_since())
+        });
+        assert_eq!(Some(Duration::from_secs(2)), enabled_since());
+
+        let replay_file = tempfile::NamedTempFile::new().expect("create replay file");
+        fs::write(&replay_file, "1000\n2000\n").expect("write replay");
+        enable_replay(&replay_file, ReplayEnd::Hold).expect("enable replay");
+        assert_eq!(Some(Duration::from_secs(0)), enabled_since());
+        assert_eq!(Some(Duration::from_secs(1)), enabled_since());
+    }
+
+    #[test]
+    fn test_read_or_system_reported() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let (time, from_file) = read_or_system_reported(&faketime_file);
+        assert!(!from_file);
+        assert!(system_unix_time() - time < Duration::from_secs(60));
+
+        fs::write(&faketime_file, "invalid").expect("write invalid file");
+        assert!(!read_or_system_reported(&faketime_file).1);
+
+        write_millis(&faketime_file, 1_000).expect("write millis");
+        assert_eq!(
+            (Duration::from_secs(1), true),
+            read_or_system_reported(&faketime_file)
+        );
+        assert_eq!(Duration::from_secs(1), read_or_system(&faketime_file));
+    }
+
+    #[test]
+    fn test_enable_with_default() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let default = Duration::from_millis(5_000);
+        assert_eq!(default, read_or_default(&faketime_file, default));
+        fs::write(&faketime_file, "invalid").expect("write invalid file");
+        assert_eq!(default, read_or_default(&faketime_file, default));
+
+        enable_with_default(&faketime_file, default);
+        assert_eq!(default, unix_time());
+        assert_eq!(5_000, set_millis(1_000).expect("set millis"));
+        assert_eq!(Duration::from_millis(1_000), unix_time());
+        fs::remove_file(&faketime_file).expect("remove file");
+        assert_eq!(default, unix_time());
+        assert_eq!(
+            format!(
+                "enabled with file {} defaulting to 5000ms",
+                faketime_file.display()
+            ),
+            describe()
+        );
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        let monotonic_file = millis_tempfile(2_000).expect("create monotonic file");
+        enable(&faketime_file);
+        enable_monotonic_file(&monotonic_file);
+        let snapshot = snapshot();
+
+        enable_millis(3_000);
+        disable_monotonic_file();
+        restore(&snapshot);
+        assert_eq!(Duration::from_secs(1), unix_time());
+        assert_eq!(Duration::from_secs(2), monotonic());
+        assert_eq!(
+            faketime_file.to_path_buf(),
+            current_path().expect("current path")
+        );
+
+        enable_offset(-1_000);
+        let snapshot = super::snapshot();
+        disable();
+        restore(&snapshot);
+        assert_eq!("enabled with an offset of -1000ms", describe());
+
+        enable_jitter(1_000, 42, 5);
+        unix_time();
+        let snapshot = super::snapshot();
+        let expected = unix_time();
+        restore(&snapshot);
+        assert_eq!(expected, unix_time());
+
+        enable_scaled(1_000, 0.0);
+        let snapshot = super::snapshot();
+        disable();
+        restore(&snapshot);
+        assert_eq!(Duration::from_secs(1), unix_time());
+    }
+
+    #[test]
+    fn test_snapshot_file_handle() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable_readonly(&faketime_file).expect("enable read-only");
+        let snapshot = snapshot();
+        write_millis(&faketime_file, 2_000).expect("write millis");
+        restore(&snapshot);
+        assert_eq!("frozen at 1000ms", describe());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_serialization() {
+        let path = PathBuf::from("/path/to/faketime");
+        enable_monotonic(&path);
+        let snapshot = snapshot();
+        let json = serde_json::to_string(&snapshot).expect("serialize snapshot");
+        assert_eq!(
+            snapshot,
+            serde_json::from_str::<FaketimeSnapshot>(&json).expect("deserialize snapshot")
+        );
+
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let path = tempdir.path().join("replay");
+        fs::write(&path, "1000\n2000\n").expect("write replay log");
+        enable_replay(&path, ReplayEnd::Loop).expect("enable replay");
+        unix_time();
+        let json = serde_json::to_string(&super::snapshot()).expect("serialize snapshot");
+        disable();
+        restore(&serde_json::from_str(&json).expect("deserialize snapshot"));
+        assert_eq!(Duration::from_secs(2), unix_time());
+        assert_eq!(Duration::from_secs(1), unix_time());
+    }
+
+    #[test]
+    fn test_describe() {
+        let faketime_file = millis_tempfile(123_456).expect("create faketime file");
+        enable(&faketime_file);
+        assert_eq!(
+            format!("enabled with file {}", faketime_file.display()),
+            describe()
+        );
+        enable_readonly(&faketime_file).expect("enable read-only");
+        assert_eq!("enabled read-only", describe());
+        enable_offset(-1_000);
+        assert_eq!("enabled with an offset of -1000ms", describe());
+        enable_jitter(100, 42, 5);
+        assert_eq!("enabled with jitter of 5ms around 100ms", describe());
+        with_frozen(1_000, || assert_eq!("frozen at 1000ms", describe()));
+        disable();
+        assert_eq!("disabled", describe());
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        let noon = 1_577_880_000_000;
+        assert_eq!(noon, parse_millis("2020-01-01T12:00:00Z").expect("parse"));
+        assert_eq!(noon, parse_millis("2020-01-01T12:00:00z").expect("parse"));
+        assert_eq!(
+            noon,
+            parse_millis("2020-01-01T20:00:00+08:00").expect("parse")
+        );
+        assert_eq!(
+            noon,
+            parse_millis("2020-01-01T07:30:00-04:30").expect("parse")
+        );
+        assert_eq!(
+            noon + 123,
+            parse_millis("2020-01-01T12:00:00.123456Z").expect("parse")
+        );
+        assert_eq!(
+            noon + 500,
+            parse_millis("2020-01-01T12:00:00.5Z").expect("parse")
+        );
+        assert_eq!(0, parse_millis("1970-01-01T00:00:00Z").expect("parse"));
+
+        assert!(parse_millis("1970-01-01T00:00:00+00:01").is_err());
+        assert!(parse_millis("2020-01-01T12:00:00").is_err());
+        assert!(parse_millis("2020-01-01T24:00:00Z").is_err());
+        assert!(parse_millis("2020-01-01T12:00:00.Z").is_err());
+        assert!(parse_millis("2020-01-01T12:00Z").is_err());
+        assert!(parse_millis("2020-01-01T12:00:00+0800").is_err());
+    }
+
+    #[test]
+    fn test_read_rfc3339_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let rfc3339_file = tempdir.path().join("rfc3339");
+        let millis_file = tempdir.path().join("millis");
+
+        fs::write(&rfc3339_file, "2020-01-01T12:00:00Z\n").expect("write timestamp");
+        fs::write(&millis_file, "1577880000000\n").expect("write millis");
+        assert_eq!(
+            read_millis(&millis_file).expect("read millis"),
+            read_millis(&rfc3339_file).expect("read timestamp")
+        );
+    }
+
+    #[test]
+    fn test_read_date_file() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+
+        fs::write(&faketime_file, "2020-01-01\n").expect("write date");
+        assert_eq!(
+            1_577_836_800_000,
+            read_millis(&faketime_file).expect("read millis")
+        );
     }
 }
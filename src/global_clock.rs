@@ -0,0 +1,153 @@
+//! Stores the clock installed via `install_as_global_default`, which is read on every `unix_time`
+//! call without taking any lock.
+//!
+//! It is a hazard pointer scheme. Before using the clock, a reader publishes the pointer in a
+//! slot owned by its thread. A writer swaps the pointer, then frees the replaced clocks which are
+//! not published in any slot. The replaced clocks which are still in use are retired, and freed
+//! by a later `store` once the readers are done with them.
+
+use crate::clock::Clock;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+pub(crate) type SharedClock = Arc<dyn Clock + Send + Sync>;
+
+/// The installed clock, or null. It is created via `Box::into_raw` in `store`.
+static CURRENT: AtomicPtr<SharedClock> = AtomicPtr::new(ptr::null_mut());
+
+/// The slots of all threads. A slot is reused after its thread exits, so the slots are bounded
+/// by the number of threads alive at the same time. Taken only by writers and new threads.
+static SLOTS: Mutex<Vec<&'static Slot>> = Mutex::new(Vec::new());
+
+/// The replaced clocks which are still published by readers. It also serializes the writers.
+static RETIRED: Mutex<Retired> = Mutex::new(Retired(Vec::new()));
+
+thread_local! {
+    static SLOT: SlotGuard = SlotGuard(Slot::acquire());
+}
+
+struct Slot {
+    published: AtomicPtr<SharedClock>,
+    in_use: AtomicBool,
+}
+
+impl Slot {
+    fn acquire() -> &'static Slot {
+        let mut slots = lock(&SLOTS);
+        let free = slots.iter().find(|slot| {
+            slot.in_use
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        });
+        match free {
+            Some(slot) => slot,
+            None => {
+                let slot = Box::leak(Box::new(Slot {
+                    published: AtomicPtr::new(ptr::null_mut()),
+                    in_use: AtomicBool::new(true),
+                }));
+                slots.push(slot);
+                slot
+            }
+        }
+    }
+}
+
+/// Releases the slot of an exiting thread.
+struct SlotGuard(&'static Slot);
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        self.0.in_use.store(false, Ordering::Release);
+    }
+}
+
+/// Unpublishes the clock, even if the clock panics.
+struct Published(&'static Slot);
+
+impl Drop for Published {
+    fn drop(&mut self) {
+        self.0.published.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+struct Retired(Vec<*mut SharedClock>);
+
+// SAFETY: the pointers are owned boxes of `SharedClock`, which is `Send`.
+unsafe impl Send for Retired {}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reads the installed clock via `f`, or returns `None` if no clock is installed.
+pub(crate) fn with<R>(f: impl FnOnce(&SharedClock) -> R) -> Option<R> {
+    if CURRENT.load(Ordering::Acquire).is_null() {
+        return None;
+    }
+    match SLOT.try_with(|slot| slot.0) {
+        Ok(slot) => with_slot(slot, f),
+        // The thread local slot is gone while the thread exits. Holds the writers off instead.
+        Err(_) => {
+            let _retired = lock(&RETIRED);
+            // SAFETY: the replaced clocks are only freed after they are taken out of `CURRENT`
+            // while holding `RETIRED`.
+            unsafe { CURRENT.load(Ordering::Acquire).as_ref() }.map(f)
+        }
+    }
+}
+
+fn with_slot<R>(slot: &'static Slot, f: impl FnOnce(&SharedClock) -> R) -> Option<R> {
+    let outer = slot.published.load(Ordering::Relaxed);
+    if !outer.is_null() {
+        // SAFETY: the clock is still published by the outer read in current thread.
+        return Some(f(unsafe { &*outer }));
+    }
+    let mut clock = CURRENT.load(Ordering::SeqCst);
+    loop {
+        if clock.is_null() {
+            return None;
+        }
+        slot.published.store(clock, Ordering::SeqCst);
+        let current = CURRENT.load(Ordering::SeqCst);
+        if current == clock {
+            break;
+        }
+        clock = current;
+    }
+    let _published = Published(slot);
+    // SAFETY: the clock is published before it is confirmed to be current, so `store` does not
+    // free it until it is unpublished.
+    Some(f(unsafe { &*clock }))
+}
+
+/// Installs the clock, or uninstalls the current one if `clock` is `None`, and drops the
+/// replaced clocks which are no longer read.
+pub(crate) fn store(clock: Option<SharedClock>) {
+    let clock = clock.map_or(ptr::null_mut(), |clock| Box::into_raw(Box::new(clock)));
+    let mut unused = Vec::new();
+    {
+        let mut retired = lock(&RETIRED);
+        let replaced = CURRENT.swap(clock, Ordering::SeqCst);
+        if !replaced.is_null() {
+            retired.0.push(replaced);
+        }
+        let published: Vec<_> = lock(&SLOTS)
+            .iter()
+            .map(|slot| slot.published.load(Ordering::SeqCst))
+            .collect();
+        retired.0.retain(|clock| {
+            let in_use = published.contains(clock);
+            if !in_use {
+                unused.push(*clock);
+            }
+            in_use
+        });
+    }
+    // Drops outside of the locks, because dropping a clock may install another one.
+    for clock in unused {
+        // SAFETY: the clock is neither current nor published, and it is removed from `RETIRED`.
+        drop(unsafe { Box::from_raw(clock) });
+    }
+}
@@ -0,0 +1,142 @@
+//! Provides `FakeInstant`, a drop-in replacement of `std::time::Instant` which reads the faked
+//! clock.
+
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::time::Duration;
+
+/// A point in the faked time read via `unix_time`, which mimics `std::time::Instant`.
+///
+/// The instants are ordered by the faked time when they are captured. Unlike
+/// `std::time::Instant`, the arithmetic never panics: subtracting a later instant, or subtracting
+/// a duration beyond *UNIX EPOCH*, saturates at zero, and adding a duration saturates at the
+/// maximum time.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use faketime::FakeInstant;
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_000);
+/// let start = FakeInstant::now();
+/// faketime::advance(Duration::from_secs(2)).expect("advance");
+/// let end = FakeInstant::now();
+/// assert!(start < end);
+/// assert_eq!(Duration::from_secs(2), end - start);
+/// assert_eq!(Duration::ZERO, start - end);
+/// assert_eq!(end, start + Duration::from_secs(2));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FakeInstant(Duration);
+
+impl FakeInstant {
+    /// Captures the current time via `unix_time`.
+    pub fn now() -> FakeInstant {
+        FakeInstant(crate::unix_time())
+    }
+
+    /// Gets the elapsed time from `earlier` to this instant, or zero if `earlier` is later.
+    pub fn duration_since(&self, earlier: FakeInstant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Gets the elapsed time since this instant, or zero if the time has gone backward.
+    pub fn elapsed(&self) -> Duration {
+        FakeInstant::now().duration_since(*self)
+    }
+
+    /// See `std::time::Instant::checked_add`.
+    pub fn checked_add(&self, duration: Duration) -> Option<FakeInstant> {
+        self.0.checked_add(duration).map(FakeInstant)
+    }
+
+    /// See `std::time::Instant::checked_sub`.
+    pub fn checked_sub(&self, duration: Duration) -> Option<FakeInstant> {
+        self.0.checked_sub(duration).map(FakeInstant)
+    }
+}
+
+impl Sub<FakeInstant> for FakeInstant {
+    type Output = Duration;
+
+    fn sub(self, earlier: FakeInstant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+impl Add<Duration> for FakeInstant {
+    type Output = FakeInstant;
+
+    fn add(self, duration: Duration) -> FakeInstant {
+        FakeInstant(self.0.saturating_add(duration))
+    }
+}
+
+impl AddAssign<Duration> for FakeInstant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for FakeInstant {
+    type Output = FakeInstant;
+
+    fn sub(self, duration: Duration) -> FakeInstant {
+        FakeInstant(self.0.saturating_sub(duration))
+    }
+}
+
+impl SubAssign<Duration> for FakeInstant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+#[cfg(all(test, not(disable_faketime)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering() {
+        crate::enable_millis(10_000);
+        let earlier = FakeInstant::now();
+        crate::set_millis(12_500).expect("set millis");
+        let later = FakeInstant::now();
+        assert!(earlier < later);
+        assert_eq!(
+            Some(std::cmp::Ordering::Greater),
+            later.partial_cmp(&earlier)
+        );
+        assert_eq!(later, earlier.max(later));
+        assert_eq!(Duration::from_millis(2_500), later - earlier);
+        assert_eq!(Duration::ZERO, earlier - later);
+
+        crate::set_millis(5_000).expect("set millis");
+        assert!(FakeInstant::now() < earlier);
+        assert_eq!(Duration::ZERO, earlier.elapsed());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        crate::enable_millis(10_000);
+        let now = FakeInstant::now();
+        assert_eq!(
+            FakeInstant(Duration::from_secs(13)),
+            now + Duration::from_secs(3)
+        );
+        assert_eq!(
+            FakeInstant(Duration::from_secs(7)),
+            now - Duration::from_secs(3)
+        );
+        assert_eq!(FakeInstant(Duration::ZERO), now - Duration::MAX);
+        assert_eq!(FakeInstant(Duration::MAX), now + Duration::MAX);
+        assert_eq!(None, now.checked_sub(Duration::from_secs(11)));
+        assert_eq!(None, now.checked_add(Duration::MAX));
+
+        let mut instant = now;
+        instant += Duration::from_secs(1);
+        instant -= Duration::from_secs(2);
+        assert_eq!(Duration::from_secs(1), now - instant);
+    }
+}
@@ -2,16 +2,118 @@
 //! not the rust cfg `disable_faketime` is set. See details in the module document of
 //! [faketime::faketime](faketime/index.html).
 
+mod anchor;
+#[cfg(all(feature = "proptest", not(disable_faketime)))]
+mod arbitrary;
+pub mod clock;
+pub mod convert;
+mod deadline;
 #[cfg(not(disable_faketime))]
 pub mod faketime;
+#[cfg(not(disable_faketime))]
+mod global_clock;
+mod instant;
+#[cfg(all(feature = "metrics", not(disable_faketime)))]
+pub mod metrics;
+#[cfg(not(disable_faketime))]
+mod notifier;
+mod random;
+pub mod rate_limiter;
+#[cfg(all(feature = "registry", not(disable_faketime)))]
+mod registry;
+mod stopwatch;
 pub mod system;
+mod system_time;
+#[cfg(feature = "test-util")]
+mod test_util;
+mod timestamp;
+#[cfg(all(feature = "futures", not(disable_faketime)))]
+mod until;
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 mod wasm;
 
+pub use crate::anchor::{anchor, FakeAnchor};
+#[cfg(all(feature = "proptest", not(disable_faketime)))]
+pub use crate::arbitrary::{monotonic_millis, FaketimeConfig};
+pub use crate::deadline::Deadline;
+#[cfg(not(disable_faketime))]
+pub use crate::faketime::{
+    add_env_key, advance, advance_locked, age_of_file, call_count, clear_all, clear_bounds,
+    command_env, cycle_position, describe, disable, disable_monotonic_file, duration_tempfile,
+    enable, enable_cycle, enable_duration, enable_file, enable_fresh, enable_from_args,
+    enable_from_file_or_default, enable_jitter, enable_millis, enable_millis_inheritable,
+    enable_monotonic, enable_monotonic_file, enable_named_clock, enable_offset, enable_once,
+    enable_quantized, enable_readonly, enable_replay, enable_scaled, enable_shared_file,
+    enable_system_time, enable_tail, enable_uncached, enable_with_default, enable_with_now,
+    enable_writable, enabled_since, from_millis_str, install_as_global_default, install_panic_dump,
+    millis_tempfile, millis_tempfile_in, millis_tempfile_with, monotonic, pause, poll_changes,
+    read_duration, read_or_default, read_or_system_reported, reset, reset_all, reset_call_count,
+    reset_on_panic, restore, resume, rewind, sanity_check, scope_with_faketime, scoped_env,
+    set_bounds, set_clock_closure, set_duration, set_global_path, set_millis,
+    set_millis_all_threads, set_millis_if_greater, set_verify_writes, set_write_format, snapshot,
+    system_elapsed_since_enable, tick_unique, uninstall_panic_dump, unix_time, validate_file,
+    wait_for, with_env, with_frozen, with_scaled, write, write_duration, write_millis,
+    write_millis_all, write_millis_checksummed, EnvGuard, FaketimeScope, FaketimeSnapshot, Millis,
+    ReplayEnd, ResetOnPanic, SharedClockFile, WriteFormat, Writer, SANE_MAX_MILLIS,
+    SANE_MIN_MILLIS,
+};
+pub use crate::instant::FakeInstant;
 #[cfg(not(disable_faketime))]
-pub use crate::faketime::{disable, enable, millis_tempfile, unix_time, write_millis};
+pub use crate::notifier::{change_notifier, ChangeNotifier};
+#[cfg(all(feature = "registry", not(disable_faketime)))]
+pub use crate::registry::registered_threads;
+pub use crate::stopwatch::FakeStopwatch;
 #[cfg(disable_faketime)]
-pub use crate::system::unix_time;
+pub use crate::system::{monotonic, unix_time};
+pub use crate::system_time::SystemTime;
+#[cfg(feature = "test-util")]
+pub use crate::test_util::assert_time_within;
+pub use crate::timestamp::{Timestamp, UNIX_EPOCH_MILLIS};
+#[cfg(all(feature = "futures", not(disable_faketime)))]
+pub use crate::until::{until, Until};
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Returns whether faketime is compiled in, that is, the rust cfg `disable_faketime` is not set.
+///
+/// ```
+/// println!("faketime compiled: {}", faketime::is_faketime_compiled());
+/// ```
+pub const fn is_faketime_compiled() -> bool {
+    !cfg!(disable_faketime)
+}
+
+/// Fails to compile unless the rust cfg `disable_faketime` is set, which guarantees that the
+/// binary cannot be faked.
+///
+/// Place it in the `main.rs` of the release builds, so a fakeable binary is never shipped to
+/// production by accident:
+///
+/// ```ignore
+/// #[cfg(not(debug_assertions))]
+/// faketime::assert_faketime_disabled!();
+///
+/// fn main() {
+///     println!("{:?}", faketime::unix_time());
+/// }
+/// ```
+///
+/// It is checked via a `const` assertion:
+///
+#[cfg_attr(not(disable_faketime), doc = "```compile_fail")]
+#[cfg_attr(disable_faketime, doc = "```")]
+/// faketime::assert_faketime_disabled!();
+/// ```
+#[macro_export]
+macro_rules! assert_faketime_disabled {
+    () => {
+        const _: () = assert!(
+            !$crate::is_faketime_compiled(),
+            "faketime must be disabled via the rust cfg `disable_faketime`"
+        );
+    };
+}
 
 /// Gets elapsed time in milliseconds since *UNIX EPOCH*.
 ///
@@ -27,10 +129,215 @@ pub use crate::system::unix_time;
 /// ## Panics
 ///
 /// Panics if the time is before *UNIX EPOCH*, or `u64` is not enough to store the number of
-/// milliseconds.
+/// milliseconds, which is about 584 million years after *UNIX EPOCH*.
 pub fn unix_time_as_millis() -> u64 {
+    let millis = unix_time().as_millis();
+    u64::try_from(millis)
+        .unwrap_or_else(|_| panic!("unix time of {}ms overflows u64 milliseconds", millis))
+}
+
+/// Gets elapsed time in milliseconds since *UNIX EPOCH* like `unix_time_as_millis`, but
+/// saturates at `u64::MAX` instead of panicking.
+///
+/// It is the one to use where panicking is unacceptable, such as stamping network messages.
+///
+/// ```
+/// let millis = faketime::now_millis_saturating();
+/// assert!(millis / 1000 - faketime::unix_time().as_secs() < 60);
+/// ```
+pub fn now_millis_saturating() -> u64 {
+    u64::try_from(unix_time().as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Adds `duration` to the time returned by `unix_time`, such as `now + ttl`, and returns `None`
+/// on overflow.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let expires_at = faketime::now_checked_add(Duration::from_secs(60)).expect("no overflow");
+/// assert!(expires_at > faketime::unix_time());
+/// assert_eq!(None, faketime::now_checked_add(Duration::MAX));
+/// ```
+///
+/// See `now_saturating_add` to saturate instead.
+pub fn now_checked_add(duration: Duration) -> Option<Duration> {
+    unix_time().checked_add(duration)
+}
+
+/// Subtracts `duration` from the time returned by `unix_time`, and returns `None` if the result
+/// would be before *UNIX EPOCH*.
+///
+/// See `now_saturating_sub` to saturate instead.
+pub fn now_checked_sub(duration: Duration) -> Option<Duration> {
+    unix_time().checked_sub(duration)
+}
+
+/// Adds `duration` to the time returned by `unix_time`, saturating at `Duration::MAX`.
+pub fn now_saturating_add(duration: Duration) -> Duration {
+    unix_time().saturating_add(duration)
+}
+
+/// Subtracts `duration` from the time returned by `unix_time`, saturating at *UNIX EPOCH*.
+pub fn now_saturating_sub(duration: Duration) -> Duration {
+    unix_time().saturating_sub(duration)
+}
+
+/// Gets how many times `unix_time` has been called in current thread, which is always zero when
+/// faketime is disabled via the rust cfg `disable_faketime`.
+#[cfg(disable_faketime)]
+pub fn call_count() -> u64 {
+    0
+}
+
+/// Does nothing when faketime is disabled via the rust cfg `disable_faketime`.
+#[cfg(disable_faketime)]
+pub fn reset_call_count() {}
+
+/// Gets elapsed time since a custom epoch, which is given as milliseconds since *UNIX EPOCH*.
+///
+/// ```
+/// // 2000-01-01T00:00:00Z
+/// let y2k = 946_684_800_000;
+/// let since_y2k = faketime::since_epoch(y2k).expect("after year 2000");
+/// assert!(faketime::unix_time() - since_y2k >= std::time::Duration::from_millis(y2k));
+/// ```
+///
+/// This function depends on the return result from `unix_time`. If `unix_time` is faked, this
+/// function is also faked.
+///
+/// Returns `None` if the time is before the custom epoch.
+pub fn since_epoch(epoch_millis: u64) -> Option<Duration> {
+    unix_time().checked_sub(convert::millis_to_duration(epoch_millis))
+}
+
+/// Gets the time after `delta` from now, in milliseconds since *UNIX EPOCH*.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(123_456);
+/// assert_eq!(124_456, faketime::after(Duration::from_secs(1)));
+/// # }
+/// ```
+///
+/// The result saturates at `u64::MAX`.
+///
+/// This function depends on the return result from `unix_time`. If `unix_time` is faked, this
+/// function is also faked.
+pub fn after(delta: Duration) -> u64 {
+    convert::duration_to_millis_checked(unix_time().saturating_add(delta)).unwrap_or(u64::MAX)
+}
+
+/// Gets the time left in the current window, where the windows are consecutive intervals of the
+/// length `window` starting from *UNIX EPOCH*.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let window = Duration::from_secs(60);
+/// let remaining = faketime::remaining_in_window(window);
+/// assert!(remaining > Duration::from_secs(0) && remaining <= window);
+/// ```
+///
+/// The result is in `(0, window]`: it is `window` when the time is exactly at the start of a
+/// window. Returns zero if `window` is zero.
+///
+/// This function depends on the return result from `unix_time`. If `unix_time` is faked, this
+/// function is also faked.
+pub fn remaining_in_window(window: Duration) -> Duration {
+    let window_nanos = window.as_nanos();
+    if window_nanos == 0 {
+        return Duration::from_secs(0);
+    }
+
+    let elapsed_nanos = unix_time().as_nanos() % window_nanos;
+    window
+        - Duration::new(
+            (elapsed_nanos / 1_000_000_000) as u64,
+            (elapsed_nanos % 1_000_000_000) as u32,
+        )
+}
+
+/// Gets the delay before the retry `attempt`, which grows exponentially from `base` and is capped
+/// at `cap`, with full jitter.
+///
+/// The delay is a pseudo-random duration between zero and `min(cap, base * 2^attempt)`
+/// inclusively. The randomness is derived from `unix_time` and `attempt`, so the delay is
+/// deterministic when the time is faked and frozen.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use std::time::Duration;
+///
+/// let base = Duration::from_millis(100);
+/// let cap = Duration::from_secs(10);
+/// faketime::enable_millis(123_456);
+/// let first = faketime::jittered_backoff(3, base, cap);
+/// assert!(first <= Duration::from_millis(800));
+/// assert_eq!(first, faketime::jittered_backoff(3, base, cap));
+/// # }
+/// ```
+///
+/// This function depends on the return result from `unix_time`. If `unix_time` is faked, this
+/// function is also faked.
+pub fn jittered_backoff(attempt: u32, base: Duration, cap: Duration) -> Duration {
+    let max = 2u32
+        .checked_pow(attempt)
+        .and_then(|factor| base.checked_mul(factor))
+        .map_or(cap, |delay| delay.min(cap));
+
+    let now = unix_time();
+    let mut state = now.as_secs() ^ u64::from(now.subsec_nanos()).rotate_left(32);
+    state ^= u64::from(attempt);
+    let random = u128::from(random::splitmix64(&mut state)) << 64
+        | u128::from(random::splitmix64(&mut state));
+    let nanos = random % (max.as_nanos() + 1);
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+/// Gets elapsed time since *UNIX EPOCH* as `libc::timespec`.
+///
+/// ```
+/// let now = faketime::unix_time();
+/// let timespec = faketime::as_timespec();
+/// assert!(timespec.tv_sec as u64 - now.as_secs() < 60);
+/// ```
+///
+/// This function depends on the return result from `unix_time`. If `unix_time` is faked, this
+/// function is also faked. It is available on unix with the cargo feature `libc`.
+#[cfg(all(unix, feature = "libc"))]
+pub fn as_timespec() -> libc::timespec {
     let duration = unix_time();
-    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+    libc::timespec {
+        tv_sec: duration.as_secs() as libc::time_t,
+        tv_nsec: duration.subsec_nanos() as libc::c_long,
+    }
+}
+
+/// Gets elapsed time since *UNIX EPOCH* as `libc::timeval`.
+///
+/// ```
+/// let now = faketime::unix_time();
+/// let timeval = faketime::as_timeval();
+/// assert!(timeval.tv_sec as u64 - now.as_secs() < 60);
+/// ```
+///
+/// This function depends on the return result from `unix_time`. If `unix_time` is faked, this
+/// function is also faked. It is available on unix with the cargo feature `libc`.
+#[cfg(all(unix, feature = "libc"))]
+pub fn as_timeval() -> libc::timeval {
+    let duration = unix_time();
+    libc::timeval {
+        tv_sec: duration.as_secs() as libc::time_t,
+        tv_usec: duration.subsec_micros() as libc::suseconds_t,
+    }
 }
 
 #[cfg(test)]
@@ -45,6 +352,20 @@ mod tests {
         assert!((now - system_now).as_secs() < 60);
     }
 
+    #[cfg(disable_faketime)]
+    #[test]
+    fn test_call_count() {
+        unix_time();
+        reset_call_count();
+        assert_eq!(0, call_count());
+    }
+
+    #[test]
+    fn test_is_faketime_compiled() {
+        const COMPILED: bool = is_faketime_compiled();
+        assert_eq!(cfg!(not(disable_faketime)), COMPILED);
+    }
+
     #[cfg(not(disable_faketime))]
     #[test]
     fn test_faketime() {
@@ -55,4 +376,149 @@ mod tests {
         assert_eq!(123, now.as_secs());
         assert_eq!(456, now.subsec_millis());
     }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_unix_time_as_millis() {
+        enable_millis(u64::MAX);
+        assert_eq!(u64::MAX, unix_time_as_millis());
+        let secs = Duration::from_secs(u64::MAX / 1_000);
+        enable_duration(secs + Duration::from_micros(u64::MAX % 1_000 * 1_000 + 999));
+        assert_eq!(u64::MAX, unix_time_as_millis());
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    #[should_panic(expected = "overflows u64 milliseconds")]
+    fn test_unix_time_as_millis_overflow() {
+        enable_duration(Duration::from_millis(u64::MAX) + Duration::from_millis(1));
+        unix_time_as_millis();
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_now_millis_saturating() {
+        enable_millis(123_456);
+        assert_eq!(123_456, now_millis_saturating());
+        enable_duration(Duration::MAX);
+        assert_eq!(u64::MAX, now_millis_saturating());
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_now_checked_arithmetic() {
+        let max_millis = Duration::from_millis(u64::MAX);
+        enable_millis(u64::MAX);
+        assert_eq!(Some(max_millis), now_checked_add(Duration::from_secs(0)));
+        assert_eq!(
+            Some(max_millis + Duration::from_secs(1)),
+            now_checked_add(Duration::from_secs(1))
+        );
+        assert_eq!(Some(Duration::from_secs(0)), now_checked_sub(max_millis));
+
+        enable_duration(Duration::MAX);
+        assert_eq!(Some(Duration::MAX), now_checked_add(Duration::from_secs(0)));
+        assert_eq!(None, now_checked_add(Duration::from_nanos(1)));
+        assert_eq!(Duration::MAX, now_saturating_add(Duration::from_nanos(1)));
+        assert_eq!(Some(Duration::from_secs(0)), now_checked_sub(Duration::MAX));
+
+        enable_millis(1_000);
+        assert_eq!(
+            Some(Duration::from_secs(0)),
+            now_checked_sub(Duration::from_secs(1))
+        );
+        assert_eq!(None, now_checked_sub(Duration::from_nanos(1_000_000_001)));
+        assert_eq!(
+            Duration::from_secs(0),
+            now_saturating_sub(Duration::from_secs(2))
+        );
+        assert_eq!(
+            Duration::from_secs(3),
+            now_saturating_add(Duration::from_secs(2))
+        );
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_since_epoch() {
+        let faketime_file = millis_tempfile(123_456).expect("create faketime file");
+        enable(&faketime_file);
+
+        assert_eq!(Some(Duration::from_millis(456)), since_epoch(123_000));
+        assert_eq!(Some(Duration::from_millis(0)), since_epoch(123_456));
+        assert_eq!(None, since_epoch(123_457));
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_jittered_backoff() {
+        let base = Duration::from_millis(100);
+        let cap = Duration::from_secs(1);
+        enable_millis(1_000);
+        let first: Vec<_> = (0..10).map(|i| jittered_backoff(i, base, cap)).collect();
+        let second: Vec<_> = (0..10).map(|i| jittered_backoff(i, base, cap)).collect();
+        assert_eq!(first, second);
+        for (attempt, delay) in first.iter().enumerate() {
+            assert!(*delay <= (base * 2u32.pow(attempt as u32)).min(cap));
+        }
+        assert_eq!(
+            Duration::from_secs(0),
+            jittered_backoff(0, Duration::from_secs(0), cap)
+        );
+        assert_eq!(
+            Duration::from_secs(0),
+            jittered_backoff(100, base, Duration::from_secs(0))
+        );
+        assert!(jittered_backoff(u32::MAX, Duration::MAX, Duration::MAX) <= Duration::MAX);
+
+        enable_millis(2_000);
+        let other: Vec<_> = (0..10).map(|i| jittered_backoff(i, base, cap)).collect();
+        assert_ne!(first, other);
+    }
+
+    #[cfg(all(unix, feature = "libc", not(disable_faketime)))]
+    #[test]
+    fn test_timespec_and_timeval() {
+        let faketime_file = millis_tempfile(123_456).expect("create faketime file");
+        enable(&faketime_file);
+
+        let timespec = as_timespec();
+        assert_eq!(123, timespec.tv_sec);
+        assert_eq!(456_000_000, timespec.tv_nsec);
+        let timeval = as_timeval();
+        assert_eq!(123, timeval.tv_sec);
+        assert_eq!(456_000, timeval.tv_usec);
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_after() {
+        enable_millis(1_000);
+        assert_eq!(1_000, after(Duration::from_secs(0)));
+        assert_eq!(1_001, after(Duration::from_micros(1_999)));
+        assert_eq!(u64::MAX, after(Duration::from_millis(u64::MAX)));
+        assert_eq!(u64::MAX, after(Duration::MAX));
+    }
+
+    #[cfg(not(disable_faketime))]
+    #[test]
+    fn test_remaining_in_window() {
+        let faketime_file = millis_tempfile(120_000).expect("create faketime file");
+        enable(&faketime_file);
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(minute, remaining_in_window(minute));
+        write_millis(&faketime_file, 120_001).expect("write millis");
+        assert_eq!(Duration::from_millis(59_999), remaining_in_window(minute));
+        write_millis(&faketime_file, 179_999).expect("write millis");
+        assert_eq!(Duration::from_millis(1), remaining_in_window(minute));
+        assert_eq!(
+            Duration::from_millis(1),
+            remaining_in_window(Duration::from_millis(2))
+        );
+        assert_eq!(
+            Duration::from_secs(0),
+            remaining_in_window(Duration::from_secs(0))
+        );
+    }
 }
@@ -0,0 +1,102 @@
+//! Counters of the timestamp file reads, enabled via the cargo feature `metrics`.
+//!
+//! The counters are process wide and cover all the reads of timestamp files in `unix_time`,
+//! including the ones from `clock::FileClock`. There is no overhead when the feature is
+//! disabled.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds, in microseconds and exclusive, of the buckets in
+/// `Metrics::read_duration_buckets`. The last bucket counts the rest reads.
+pub const READ_DURATION_BOUNDS_MICROS: [u64; 6] = [1, 10, 100, 1_000, 10_000, 100_000];
+
+const BUCKETS_COUNT: usize = READ_DURATION_BOUNDS_MICROS.len() + 1;
+
+static FILE_READS: AtomicU64 = AtomicU64::new(0);
+static FALLBACKS: AtomicU64 = AtomicU64::new(0);
+static READ_DURATION_BUCKETS: [AtomicU64; BUCKETS_COUNT] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// A snapshot of the counters.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of timestamp file reads.
+    pub file_reads: u64,
+    /// Number of reads which failed and fell back to the system time.
+    pub fallbacks: u64,
+    /// Histogram of the read durations, see `READ_DURATION_BOUNDS_MICROS`.
+    pub read_duration_buckets: [u64; BUCKETS_COUNT],
+}
+
+/// Gets the current values of the counters.
+pub fn snapshot() -> Metrics {
+    let mut read_duration_buckets = [0; BUCKETS_COUNT];
+    for (value, bucket) in read_duration_buckets
+        .iter_mut()
+        .zip(READ_DURATION_BUCKETS.iter())
+    {
+        *value = bucket.load(Ordering::Relaxed);
+    }
+    Metrics {
+        file_reads: FILE_READS.load(Ordering::Relaxed),
+        fallbacks: FALLBACKS.load(Ordering::Relaxed),
+        read_duration_buckets,
+    }
+}
+
+/// Resets all the counters to zero.
+pub fn reset() {
+    FILE_READS.store(0, Ordering::Relaxed);
+    FALLBACKS.store(0, Ordering::Relaxed);
+    for bucket in READ_DURATION_BUCKETS.iter() {
+        bucket.store(0, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn record_read(duration: Duration) {
+    let micros = duration.as_micros();
+    let index = READ_DURATION_BOUNDS_MICROS
+        .iter()
+        .position(|bound| micros < u128::from(*bound))
+        .unwrap_or(BUCKETS_COUNT - 1);
+    FILE_READS.fetch_add(1, Ordering::Relaxed);
+    READ_DURATION_BUCKETS[index].fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_fallback() {
+    FALLBACKS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_counter() {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        crate::enable(tempdir.path().join("missing"));
+
+        let before = snapshot();
+        crate::unix_time();
+        let after = snapshot();
+        assert!(after.file_reads > before.file_reads);
+        assert!(after.fallbacks > before.fallbacks);
+        assert!(after.read_duration_buckets.iter().sum::<u64>() > 0);
+    }
+
+    #[test]
+    fn test_record_read() {
+        let before = snapshot().read_duration_buckets;
+        record_read(Duration::from_secs(1));
+        let after = snapshot().read_duration_buckets;
+        assert!(after[BUCKETS_COUNT - 1] > before[BUCKETS_COUNT - 1]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Notifies the changes of the timestamp file, so an event loop can wait for the faked time to
+//! advance instead of polling it.
+//!
+//! On Linux with the cargo feature `libc`, the notifier is backed by inotify and exposes a file
+//! descriptor, which becomes readable when the timestamp file changes. It can be registered into
+//! a reactor such as `mio` or `tokio`. On other platforms, there is no file descriptor, and
+//! `ChangeNotifier::changed` polls the file content instead.
+
+use std::io::Result;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+/// Watches the timestamp file of the thread which has created it, see `change_notifier`.
+pub struct ChangeNotifier {
+    path: PathBuf,
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    inotify: inotify::Inotify,
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    last: Option<u64>,
+}
+
+/// Creates a notifier which watches the timestamp file of current thread.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+///
+/// let mut notifier = faketime::change_notifier().expect("create notifier");
+/// assert!(!notifier.changed().expect("check changes"));
+/// faketime::write_millis(&faketime_file, 2_000).expect("write millis");
+/// assert!(notifier.changed().expect("check changes"));
+/// ```
+///
+/// Returns an error if faketime is not enabled with a timestamp file path in current thread, or
+/// the file cannot be watched.
+pub fn change_notifier() -> Result<ChangeNotifier> {
+    ChangeNotifier::new(crate::faketime::current_path()?)
+}
+
+impl ChangeNotifier {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn new(path: PathBuf) -> Result<Self> {
+        let inotify = inotify::Inotify::watch(&path)?;
+        Ok(ChangeNotifier { path, inotify })
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    fn new(path: PathBuf) -> Result<Self> {
+        let last = crate::faketime::read_millis(&path).ok();
+        Ok(ChangeNotifier { path, last })
+    }
+
+    /// Gets the path of the watched timestamp file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Checks whether the timestamp file has changed since the last check, or since the notifier
+    /// was created.
+    ///
+    /// With inotify, it consumes the pending events, so the file descriptor is no longer readable
+    /// until the next change. Otherwise, it compares the time in the file with the last read one.
+    pub fn changed(&mut self) -> Result<bool> {
+        #[cfg(all(target_os = "linux", feature = "libc"))]
+        {
+            self.inotify.drain(&self.path)
+        }
+        #[cfg(not(all(target_os = "linux", feature = "libc")))]
+        {
+            let current = crate::faketime::read_millis(&self.path).ok();
+            let changed = current != self.last;
+            self.last = current;
+            Ok(changed)
+        }
+    }
+
+    /// Gets the file descriptor which becomes readable when the timestamp file changes.
+    ///
+    /// It is only available on Linux with the cargo feature `libc`, and returns `None` elsewhere,
+    /// in which case the caller has to poll `changed` periodically.
+    #[cfg(unix)]
+    pub fn as_raw_fd(&self) -> Option<RawFd> {
+        #[cfg(all(target_os = "linux", feature = "libc"))]
+        {
+            Some(self.inotify.as_raw_fd())
+        }
+        #[cfg(not(all(target_os = "linux", feature = "libc")))]
+        {
+            None
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "libc"))]
+mod inotify {
+    use std::ffi::{CString, OsStr};
+    use std::io::{Error, ErrorKind, Result};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::path::Path;
+
+    /// Size of `struct inotify_event` without the trailing name.
+    const EVENT_HEADER_LEN: usize = 16;
+
+    pub struct Inotify(OwnedFd);
+
+    impl Inotify {
+        /// Watches the parent directory, because `write_millis` replaces the file by renaming.
+        pub fn watch(path: &Path) -> Result<Self> {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let dir = CString::new(dir.as_os_str().as_bytes())
+                .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(Error::last_os_error());
+            }
+            let inotify = Inotify(unsafe { OwnedFd::from_raw_fd(fd) });
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_DELETE;
+            if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+                return Err(Error::last_os_error());
+            }
+            Ok(inotify)
+        }
+
+        /// Consumes all the pending events and returns whether any of them is about the file.
+        pub fn drain(&self, path: &Path) -> Result<bool> {
+            let name = path.file_name().map(OsStr::as_bytes);
+            let mut changed = false;
+            let mut buf = [0u8; 4096];
+            loop {
+                let len = unsafe {
+                    libc::read(
+                        self.0.as_raw_fd(),
+                        buf.as_mut_ptr() as *mut libc::c_void,
+                        buf.len(),
+                    )
+                };
+                if len < 0 {
+                    let err = Error::last_os_error();
+                    return match err.kind() {
+                        ErrorKind::WouldBlock => Ok(changed),
+                        ErrorKind::Interrupted => continue,
+                        _ => Err(err),
+                    };
+                }
+
+                let mut events = &buf[..len as usize];
+                while events.len() >= EVENT_HEADER_LEN {
+                    let mut name_len = [0u8; 4];
+                    name_len.copy_from_slice(&events[12..EVENT_HEADER_LEN]);
+                    let end = EVENT_HEADER_LEN + u32::from_ne_bytes(name_len) as usize;
+                    let event_name = events[EVENT_HEADER_LEN..end]
+                        .split(|byte| *byte == 0)
+                        .next();
+                    changed |= event_name == name;
+                    events = &events[end..];
+                }
+            }
+        }
+
+        pub fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_notifier() {
+        crate::disable();
+        assert!(change_notifier().is_err());
+
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let faketime_file = tempdir.path().join("faketime");
+        let other_file = tempdir.path().join("other");
+        crate::write_millis(&faketime_file, 1_000).expect("write millis");
+        crate::enable(&faketime_file);
+
+        let mut notifier = change_notifier().expect("create notifier");
+        assert_eq!(faketime_file, notifier.path());
+        assert!(!notifier.changed().expect("check changes"));
+        crate::write_millis(&other_file, 1_000).expect("write millis");
+        assert!(!notifier.changed().expect("check changes"));
+        crate::write_millis(&faketime_file, 2_000).expect("write millis");
+        assert!(notifier.changed().expect("check changes"));
+        assert!(!notifier.changed().expect("check changes"));
+    }
+
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    #[test]
+    fn test_change_notifier_fd() {
+        let wait = |fd: RawFd| {
+            let mut pollfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            unsafe { libc::poll(&mut pollfd, 1, 1_000) }
+        };
+
+        let faketime_file = crate::millis_tempfile(1_000).expect("create faketime file");
+        crate::enable(&faketime_file);
+        let mut notifier = change_notifier().expect("create notifier");
+        let fd = notifier.as_raw_fd().expect("inotify fd");
+
+        crate::write_millis(&faketime_file, 2_000).expect("write millis");
+        assert_eq!(1, wait(fd));
+        assert!(notifier.changed().expect("check changes"));
+    }
+}
@@ -0,0 +1,163 @@
+//! A token bucket rate limiter which reads the time only via the injected `Clock`.
+//!
+//! It is a reference of writing components against the `Clock` trait, which can be tested with
+//! `FakeClock` deterministically.
+
+use crate::clock::Clock;
+use std::time::Duration;
+
+/// Allows bursts up to `capacity` requests, and refills one token every `interval`.
+///
+/// ```
+/// use faketime::clock::FakeClock;
+/// use faketime::rate_limiter::RateLimiter;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let clock = Arc::new(FakeClock::new(0));
+/// let mut limiter = RateLimiter::new(Arc::clone(&clock), 2, Duration::from_secs(1));
+/// assert!(limiter.allow());
+/// assert!(limiter.allow());
+/// assert!(!limiter.allow());
+///
+/// clock.advance(Duration::from_secs(1));
+/// assert!(limiter.allow());
+/// assert!(!limiter.allow());
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter<C: Clock> {
+    clock: C,
+    capacity: u64,
+    interval: Duration,
+    tokens: u64,
+    /// The time when the tokens were last refilled.
+    refilled_at: Duration,
+}
+
+impl<C: Clock> RateLimiter<C> {
+    /// Creates a rate limiter with a full bucket.
+    ///
+    /// If `interval` is zero, the bucket is refilled immediately and all the requests are allowed
+    /// unless `capacity` is zero.
+    pub fn new(clock: C, capacity: u64, interval: Duration) -> Self {
+        let refilled_at = clock.unix_time();
+        RateLimiter {
+            clock,
+            capacity,
+            interval,
+            tokens: capacity,
+            refilled_at,
+        }
+    }
+
+    /// Takes a token if there is any, and returns whether the request is allowed.
+    pub fn allow(&mut self) -> bool {
+        self.refill();
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Gets the number of tokens left in the bucket, after refilling.
+    pub fn tokens(&mut self) -> u64 {
+        self.refill();
+        self.tokens
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.unix_time();
+        // The time is kept if the clock goes backward, so the tokens are not refilled twice.
+        let elapsed = now.saturating_sub(self.refilled_at);
+        let interval = self.interval.as_nanos();
+        let (added, remainder) = match elapsed.as_nanos().checked_div(interval) {
+            Some(added) => (added, elapsed.as_nanos() % interval),
+            None => (u128::from(self.capacity), 0),
+        };
+
+        let missing = self.capacity - self.tokens;
+        if added >= u128::from(missing) {
+            self.tokens = self.capacity;
+            self.refilled_at = self.refilled_at.max(now);
+        } else if added > 0 {
+            // `added` is less than `missing`, which is an `u64`.
+            self.tokens += added as u64;
+            let remainder = Duration::new(
+                (remainder / 1_000_000_000) as u64,
+                (remainder % 1_000_000_000) as u32,
+            );
+            self.refilled_at = now - remainder;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use std::sync::Arc;
+
+    fn new_limiter(
+        capacity: u64,
+        interval_millis: u64,
+    ) -> (Arc<FakeClock>, RateLimiter<Arc<FakeClock>>) {
+        let clock = Arc::new(FakeClock::new(10_000));
+        let limiter = RateLimiter::new(
+            Arc::clone(&clock),
+            capacity,
+            Duration::from_millis(interval_millis),
+        );
+        (clock, limiter)
+    }
+
+    #[test]
+    fn test_burst_and_refill() {
+        let (clock, mut limiter) = new_limiter(3, 100);
+        assert_eq!(3, (0..10).filter(|_| limiter.allow()).count());
+
+        clock.advance(Duration::from_millis(99));
+        assert!(!limiter.allow());
+        clock.advance(Duration::from_millis(1));
+        assert!(limiter.allow());
+        assert!(!limiter.allow());
+
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(2, limiter.tokens());
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(3, limiter.tokens());
+    }
+
+    #[test]
+    fn test_capacity_is_capped() {
+        let (clock, mut limiter) = new_limiter(2, 100);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(2, limiter.tokens());
+        assert!(limiter.allow());
+        assert!(limiter.allow());
+        assert!(!limiter.allow());
+        clock.advance(Duration::from_millis(100));
+        assert!(limiter.allow());
+    }
+
+    #[test]
+    fn test_clock_goes_backward() {
+        let (clock, mut limiter) = new_limiter(1, 100);
+        assert!(limiter.allow());
+        clock.set_millis(0);
+        assert!(!limiter.allow());
+        clock.set_millis(10_050);
+        assert!(!limiter.allow());
+        clock.set_millis(10_100);
+        assert!(limiter.allow());
+    }
+
+    #[test]
+    fn test_zero_interval_and_capacity() {
+        let (_clock, mut limiter) = new_limiter(1, 0);
+        assert!((0..10).all(|_| limiter.allow()));
+        let (_clock, mut limiter) = new_limiter(0, 100);
+        assert!(!limiter.allow());
+    }
+}
@@ -0,0 +1,117 @@
+//! Registry of the threads which have enabled faketime, enabled via the cargo feature `registry`.
+//!
+//! It is a diagnostic aid for large test harnesses. Every change of the faketime settings in a
+//! thread takes a process wide lock to update the registry, so the feature is disabled by
+//! default. Reading the time does not touch the registry.
+
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+
+static REGISTRY: Mutex<Vec<(ThreadId, Option<PathBuf>)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static GUARD: Guard = Guard(thread::current().id());
+}
+
+/// Removes the thread from the registry when the thread exits.
+struct Guard(ThreadId);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        remove(self.0);
+    }
+}
+
+/// Lists the threads which have enabled faketime, with the timestamp file paths.
+///
+/// The path is `None` if faketime is enabled without a timestamp file path, for example, via
+/// `enable_millis`. The threads which have exited are not listed.
+///
+/// It is available with the cargo feature `registry`, which takes a process wide lock on every
+/// change of the faketime settings in any thread.
+///
+/// ```
+/// let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+/// faketime::enable(&faketime_file);
+/// assert_eq!(
+///     vec![(std::thread::current().id(), Some(faketime_file.to_path_buf()))],
+///     faketime::registered_threads()
+/// );
+/// ```
+pub fn registered_threads() -> Vec<(ThreadId, Option<PathBuf>)> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Registers the current thread with the timestamp file path, or removes the current thread from
+/// the registry if `entry` is `None`.
+pub(crate) fn update(entry: Option<Option<PathBuf>>) {
+    let _ = GUARD.try_with(|guard| {
+        remove(guard.0);
+        if let Some(path) = entry {
+            REGISTRY
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((guard.0, path));
+        }
+    });
+}
+
+fn remove(id: ThreadId) {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|(registered, _)| *registered != id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_registered_threads() {
+        let faketime_file = crate::millis_tempfile(123_456).expect("create faketime file");
+        let (to_main, from_worker) = mpsc::channel();
+        let (to_worker, from_main) = mpsc::channel::<()>();
+
+        let path = faketime_file.to_path_buf();
+        let first = thread::spawn(move || {
+            crate::enable(&path);
+            to_main.send(()).expect("send");
+            let _ = from_main.recv();
+            crate::disable();
+        });
+        from_worker.recv().expect("recv");
+        let second = thread::spawn(|| {
+            crate::enable_millis(0);
+            thread::current().id()
+        })
+        .join()
+        .expect("join thread");
+
+        let first_id = first.thread().id();
+        let registered: Vec<_> = registered_threads()
+            .into_iter()
+            .filter(|(id, _)| *id == first_id || *id == second)
+            .collect();
+        assert_eq!(
+            vec![(first_id, Some(faketime_file.to_path_buf()))],
+            registered,
+            "entry of the exited thread should be removed"
+        );
+
+        crate::enable_millis(0);
+        let current = thread::current().id();
+        assert!(registered_threads().contains(&(current, None)));
+        crate::disable();
+        assert!(registered_threads().iter().all(|(id, _)| *id != current));
+
+        to_worker.send(()).expect("send");
+        first.join().expect("join thread");
+        assert!(registered_threads().iter().all(|(id, _)| *id != first_id));
+    }
+}
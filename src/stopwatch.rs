@@ -0,0 +1,69 @@
+//! Provides `FakeStopwatch`, which measures the elapsed time via `unix_time`.
+
+use std::time::Duration;
+
+/// Measures the elapsed time of the clock returned by `unix_time`.
+///
+/// Unlike `std::time::Instant`, the stopwatch obeys the faketime settings of current thread: it
+/// does not advance when the time is frozen until the time is changed manually, and it advances
+/// faster when the time is scaled.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use faketime::FakeStopwatch;
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_000);
+/// let stopwatch = FakeStopwatch::start();
+/// assert_eq!(Duration::from_secs(0), stopwatch.elapsed());
+/// faketime::advance(Duration::from_secs(2)).expect("advance");
+/// assert_eq!(Duration::from_secs(2), stopwatch.elapsed());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FakeStopwatch {
+    start: Duration,
+}
+
+impl FakeStopwatch {
+    /// Starts the stopwatch at the current time.
+    pub fn start() -> FakeStopwatch {
+        FakeStopwatch {
+            start: crate::unix_time(),
+        }
+    }
+
+    /// Gets the elapsed time since the stopwatch was started.
+    ///
+    /// It is saturated at zero if the time has gone backward.
+    pub fn elapsed(&self) -> Duration {
+        crate::unix_time().saturating_sub(self.start)
+    }
+}
+
+#[cfg(all(test, not(disable_faketime)))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_frozen_stopwatch() {
+        crate::enable_millis(1_000);
+        let stopwatch = FakeStopwatch::start();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(Duration::from_secs(0), stopwatch.elapsed());
+        crate::advance(Duration::from_millis(1_500)).expect("advance");
+        assert_eq!(Duration::from_millis(1_500), stopwatch.elapsed());
+        crate::set_millis(0).expect("set millis");
+        assert_eq!(Duration::from_secs(0), stopwatch.elapsed());
+    }
+
+    #[test]
+    fn test_scaled_stopwatch() {
+        crate::enable_scaled(1_000, 100.0);
+        let stopwatch = FakeStopwatch::start();
+        thread::sleep(Duration::from_millis(10));
+        assert!(stopwatch.elapsed() >= Duration::from_millis(1_000 - 1));
+    }
+}
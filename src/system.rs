@@ -1,10 +1,11 @@
 //! This is the fallback implementation when cfg `disable_faketime` is set.
 
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
-use crate::wasm::SystemTime;
+use crate::wasm::{Instant, SystemTime};
+use std::sync::OnceLock;
 use std::time::Duration;
 #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 
 /// Gets elapsed time since *UNIX EPOCH*.
 ///
@@ -16,3 +17,13 @@ pub fn unix_time() -> Duration {
         .duration_since(SystemTime::UNIX_EPOCH)
         .expect("SystemTime before UNIX EPOCH!")
 }
+
+/// Gets elapsed time of a monotonic clock since the anchor.
+///
+/// The anchor is the moment when this function is called for the first time in the process, so
+/// the first call returns zero. Unlike `unix_time`, the returned time never goes backward.
+pub fn monotonic() -> Duration {
+    static ANCHOR: OnceLock<Instant> = OnceLock::new();
+    let anchor = *ANCHOR.get_or_init(Instant::now);
+    Instant::now().saturating_duration_since(anchor)
+}
@@ -0,0 +1,134 @@
+//! Provides `SystemTime`, a drop-in replacement of `std::time::SystemTime` which reads the faked
+//! clock.
+
+use std::ops::{Add, Sub};
+use std::time::{Duration, SystemTimeError};
+
+/// A wrapper of `std::time::SystemTime` whose `now` reads the time via `unix_time`.
+///
+/// Existing code can be faked by replacing `use std::time::SystemTime` with
+/// `use faketime::SystemTime`.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use faketime::SystemTime;
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_000);
+/// let now = SystemTime::now();
+/// assert_eq!(
+///     Duration::from_secs(1),
+///     now.duration_since(SystemTime::UNIX_EPOCH).expect("after UNIX EPOCH")
+/// );
+/// faketime::advance(Duration::from_secs(2)).expect("advance");
+/// assert_eq!(Duration::from_secs(2), now.elapsed().expect("elapsed"));
+/// # }
+/// ```
+///
+/// It never calls `std::time::SystemTime::now`, so it also works on wasm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SystemTime(std::time::SystemTime);
+
+impl SystemTime {
+    /// See `std::time::SystemTime::UNIX_EPOCH`.
+    pub const UNIX_EPOCH: SystemTime = SystemTime(std::time::SystemTime::UNIX_EPOCH);
+
+    /// Gets the current time via `unix_time`.
+    pub fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + crate::unix_time()
+    }
+
+    /// See `std::time::SystemTime::duration_since`.
+    pub fn duration_since(&self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+        self.0.duration_since(earlier.0)
+    }
+
+    /// Gets the elapsed time since this time, where the current time is read via `unix_time`.
+    ///
+    /// Returns an error if the current time is earlier than this time.
+    pub fn elapsed(&self) -> Result<Duration, SystemTimeError> {
+        SystemTime::now().duration_since(*self)
+    }
+
+    /// See `std::time::SystemTime::checked_add`.
+    pub fn checked_add(&self, duration: Duration) -> Option<SystemTime> {
+        self.0.checked_add(duration).map(SystemTime)
+    }
+
+    /// See `std::time::SystemTime::checked_sub`.
+    pub fn checked_sub(&self, duration: Duration) -> Option<SystemTime> {
+        self.0.checked_sub(duration).map(SystemTime)
+    }
+}
+
+impl Add<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn add(self, duration: Duration) -> SystemTime {
+        SystemTime(self.0 + duration)
+    }
+}
+
+impl Sub<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn sub(self, duration: Duration) -> SystemTime {
+        SystemTime(self.0 - duration)
+    }
+}
+
+impl From<std::time::SystemTime> for SystemTime {
+    fn from(time: std::time::SystemTime) -> SystemTime {
+        SystemTime(time)
+    }
+}
+
+impl From<SystemTime> for std::time::SystemTime {
+    fn from(time: SystemTime) -> std::time::SystemTime {
+        time.0
+    }
+}
+
+#[cfg(all(test, not(disable_faketime)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_now() {
+        crate::enable_millis(123_456);
+        assert_eq!(
+            std::time::SystemTime::UNIX_EPOCH + Duration::from_millis(123_456),
+            SystemTime::now().into()
+        );
+    }
+
+    #[test]
+    fn test_duration_since() {
+        crate::enable_millis(10_000);
+        let now = SystemTime::now();
+        let earlier = now - Duration::from_secs(3);
+        assert_eq!(
+            Duration::from_secs(3),
+            now.duration_since(earlier).expect("later")
+        );
+        let err = earlier.duration_since(now).expect_err("earlier");
+        assert_eq!(Duration::from_secs(3), err.duration());
+        assert_eq!(Some(earlier), now.checked_sub(Duration::from_secs(3)));
+        assert_eq!(None, now.checked_sub(Duration::MAX));
+    }
+
+    #[test]
+    fn test_elapsed() {
+        crate::enable_millis(10_000);
+        let now = SystemTime::now();
+        assert_eq!(Duration::from_secs(0), now.elapsed().expect("elapsed"));
+        crate::advance(Duration::from_millis(1_500)).expect("advance");
+        assert_eq!(
+            Duration::from_millis(1_500),
+            now.elapsed().expect("elapsed")
+        );
+        crate::set_millis(9_000).expect("set millis");
+        assert!(now.elapsed().is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Helpers for the tests which assert the time, enabled via the cargo feature `test-util`.
+
+use std::time::Duration;
+
+/// Asserts that `unix_time` is within `tolerance` of `expected` in either direction.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use std::time::Duration;
+///
+/// faketime::enable_millis(1_500);
+/// faketime::assert_time_within(Duration::from_secs(1), Duration::from_secs(1));
+/// # }
+/// ```
+///
+/// ## Panics
+///
+/// Panics with the actual time, the expected time and the tolerance if the time is out of range.
+#[track_caller]
+pub fn assert_time_within(expected: Duration, tolerance: Duration) {
+    let actual = crate::unix_time();
+    if actual.abs_diff(expected) > tolerance {
+        panic!(
+            "time is not within tolerance: actual {:?}, expected {:?}, tolerance {:?}",
+            actual, expected, tolerance
+        );
+    }
+}
+
+#[cfg(all(test, not(disable_faketime)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assert_time_within() {
+        crate::enable_millis(10_000);
+        let tolerance = Duration::from_millis(500);
+        assert_time_within(Duration::from_millis(10_000), Duration::ZERO);
+        assert_time_within(Duration::from_millis(9_500), tolerance);
+        assert_time_within(Duration::from_millis(10_500), tolerance);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "time is not within tolerance: actual 10s, expected 10.501s, tolerance 500ms"
+    )]
+    fn test_assert_time_within_after() {
+        crate::enable_millis(10_000);
+        assert_time_within(Duration::from_millis(10_501), Duration::from_millis(500));
+    }
+
+    #[test]
+    #[should_panic(expected = "actual 10s, expected 9.499s, tolerance 500ms")]
+    fn test_assert_time_within_before() {
+        crate::enable_millis(10_000);
+        assert_time_within(Duration::from_millis(9_499), Duration::from_millis(500));
+    }
+}
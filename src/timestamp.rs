@@ -0,0 +1,65 @@
+//! Provides `Timestamp`, a point in time represented as elapsed time since *UNIX EPOCH*.
+
+use std::time::Duration;
+
+/// Milliseconds since *UNIX EPOCH* of *UNIX EPOCH* itself.
+pub const UNIX_EPOCH_MILLIS: u64 = 0;
+
+/// A point in time as elapsed time since *UNIX EPOCH*.
+///
+/// ```
+/// use faketime::Timestamp;
+///
+/// assert_eq!(Timestamp::from_secs(1), Timestamp::from_millis(1_000));
+/// assert!(Timestamp::epoch() < Timestamp::from_millis(1));
+/// assert!(Timestamp::epoch() < Timestamp::now());
+/// ```
+///
+/// `Timestamp::now` depends on the return result from `unix_time`. If `unix_time` is faked, it
+/// is also faked.
+///
+/// ```
+/// # #[cfg(not(disable_faketime))]
+/// # {
+/// use faketime::Timestamp;
+///
+/// faketime::enable_millis(123_456);
+/// assert_eq!(Timestamp::from_millis(123_456), Timestamp::now());
+/// assert!(Timestamp::from_secs(123) < Timestamp::now());
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(Duration);
+
+impl Timestamp {
+    /// Creates the timestamp of *UNIX EPOCH*.
+    pub fn epoch() -> Timestamp {
+        Timestamp::from_millis(UNIX_EPOCH_MILLIS)
+    }
+
+    /// Creates a timestamp from the milliseconds since *UNIX EPOCH*.
+    pub fn from_millis(millis: u64) -> Timestamp {
+        Timestamp(Duration::from_millis(millis))
+    }
+
+    /// Creates a timestamp from the seconds since *UNIX EPOCH*.
+    pub fn from_secs(secs: u64) -> Timestamp {
+        Timestamp(Duration::from_secs(secs))
+    }
+
+    /// Gets the current time via `unix_time`.
+    pub fn now() -> Timestamp {
+        Timestamp(crate::unix_time())
+    }
+
+    /// Gets the elapsed time since *UNIX EPOCH*.
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl From<Duration> for Timestamp {
+    fn from(duration: Duration) -> Self {
+        Timestamp(duration)
+    }
+}
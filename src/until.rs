@@ -0,0 +1,440 @@
+//! A future which resolves when the faked time reaches a target, enabled via the cargo feature
+//! `futures`.
+//!
+//! The future does not depend on any async runtime. All the pending futures share one watcher
+//! thread, which is started on the first pending poll. It wakes a task when the time of its
+//! thread may have changed, then the task reads the time again.
+//!
+//! On Linux with the cargo feature `libc`, the watcher blocks in `poll` on the inotify file
+//! descriptors of the timestamp files, so it only runs when a file changes. The futures without
+//! a file descriptor, that is, all of them on other platforms and those polled in a thread which
+//! does not read a timestamp file, are checked every `WATCH_INTERVAL` instead.
+
+use crate::faketime::{unix_time, Millis};
+use crate::notifier::{change_notifier, ChangeNotifier};
+use std::collections::HashMap;
+use std::future::Future;
+#[cfg(all(target_os = "linux", feature = "libc"))]
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+#[cfg(not(all(target_os = "linux", feature = "libc")))]
+use std::sync::Condvar;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the watcher checks the futures which have no file descriptor to wait on.
+const WATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The file descriptor the watcher waits on, which does not exist without inotify.
+#[cfg(all(target_os = "linux", feature = "libc"))]
+type Fd = RawFd;
+#[cfg(not(all(target_os = "linux", feature = "libc")))]
+type Fd = std::convert::Infallible;
+
+static WATCHER: OnceLock<Watcher> = OnceLock::new();
+
+/// Creates a future which resolves once `unix_time` of the thread polling it is at least
+/// `target_millis` milliseconds since *UNIX EPOCH*. It is the async analog of waiting for the
+/// faked time.
+///
+/// ```
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::task::{Context, Poll, Waker};
+///
+/// faketime::enable_millis(2_000);
+/// let mut until = pin!(faketime::until(1_000));
+/// let mut cx = Context::from_waker(Waker::noop());
+/// assert_eq!(Poll::Ready(()), until.as_mut().poll(&mut cx));
+/// ```
+///
+/// If faketime is enabled with a timestamp file path in the polling thread, the task is woken
+/// when the file changes, for example, when another thread or process advances the time via
+/// `write_millis` or `advance`. On Linux with the cargo feature `libc`, it is woken right after
+/// the change, and elsewhere within 10 milliseconds.
+///
+/// Otherwise, such as when the time is kept in memory via `enable_millis`, nothing can change the
+/// time from outside of the thread, and the task is woken every 10 milliseconds to read the time
+/// again, which covers the clock installed via `install_as_global_default` and the system time.
+///
+/// The time is read in the thread which polls the future, so the future should be polled in the
+/// thread where faketime is enabled, such as in a single threaded executor.
+pub fn until(target_millis: u64) -> Until {
+    Until {
+        target_millis,
+        id: None,
+    }
+}
+
+/// The future created via `until`.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Until {
+    target_millis: u64,
+    /// The id of the entry in the watcher, registered on the first pending poll.
+    id: Option<u64>,
+}
+
+impl Until {
+    fn reached(&self) -> bool {
+        Millis::from(unix_time()).0 >= self.target_millis
+    }
+
+    fn deregister(&mut self) {
+        if let Some(id) = self.id.take() {
+            watcher().deregister(id);
+        }
+    }
+}
+
+impl Future for Until {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.reached() {
+            self.deregister();
+            return Poll::Ready(());
+        }
+        match self.id {
+            Some(id) => watcher().update(id, cx.waker()),
+            None => {
+                self.id = Some(watcher().register(cx.waker().clone()));
+                // The file may have changed before the watcher started watching it.
+                if self.reached() {
+                    self.deregister();
+                    return Poll::Ready(());
+                }
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Until {
+    fn drop(&mut self) {
+        self.deregister();
+    }
+}
+
+/// Gets the watcher shared by all the futures, and starts its thread on the first call.
+fn watcher() -> &'static Watcher {
+    let mut started = false;
+    let watcher = WATCHER.get_or_init(|| {
+        started = true;
+        Watcher::new()
+    });
+    if started {
+        thread::spawn(move || watcher.run());
+    }
+    watcher
+}
+
+/// The pending futures and the signal to interrupt the watcher thread.
+struct Watcher {
+    entries: Mutex<Entries>,
+    signal: Signal,
+}
+
+#[derive(Default)]
+struct Entries {
+    next_id: u64,
+    pending: HashMap<u64, Entry>,
+}
+
+/// A pending future with the latest waker.
+struct Entry {
+    waker: Waker,
+    notifier: Option<ChangeNotifier>,
+}
+
+impl Entry {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn fd(&self) -> Option<Fd> {
+        self.notifier.as_ref().and_then(ChangeNotifier::as_raw_fd)
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    fn fd(&self) -> Option<Fd> {
+        None
+    }
+
+    /// Checks whether the task has to read the time again. It is always true without a
+    /// timestamp file.
+    fn changed(&mut self) -> bool {
+        self.notifier
+            .as_mut()
+            .is_none_or(|notifier| notifier.changed().unwrap_or(true))
+    }
+}
+
+impl Watcher {
+    fn new() -> Self {
+        Watcher {
+            entries: Mutex::default(),
+            signal: Signal::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Registers a future polled in current thread, and watches the timestamp file of the thread
+    /// if there is one.
+    fn register(&self, waker: Waker) -> u64 {
+        let notifier = change_notifier().ok();
+        let id = {
+            let mut entries = self.lock();
+            let id = entries.next_id;
+            entries.next_id += 1;
+            entries.pending.insert(id, Entry { waker, notifier });
+            id
+        };
+        self.signal.notify();
+        id
+    }
+
+    fn update(&self, id: u64, waker: &Waker) {
+        if let Some(entry) = self.lock().pending.get_mut(&id) {
+            entry.waker.clone_from(waker);
+        }
+    }
+
+    fn deregister(&self, id: u64) {
+        let entry = self.lock().pending.remove(&id);
+        // Interrupts the watcher, so it stops waiting on the closed file descriptor, which may be
+        // reused by another file.
+        if entry.is_some_and(|entry| entry.fd().is_some()) {
+            self.signal.notify();
+        }
+    }
+
+    fn run(&self) {
+        let mut last_tick = Instant::now();
+        loop {
+            let (fds, timed) = {
+                let entries = self.lock();
+                let fds: Vec<_> = entries
+                    .pending
+                    .iter()
+                    .filter_map(|(id, entry)| entry.fd().map(|fd| (*id, fd)))
+                    .collect();
+                let timed = fds.len() < entries.pending.len();
+                (fds, timed)
+            };
+            let timeout = if timed {
+                Some(WATCH_INTERVAL.saturating_sub(last_tick.elapsed()))
+            } else {
+                None
+            };
+            let ready = self.signal.wait(&fds, timeout);
+            let tick = last_tick.elapsed() >= WATCH_INTERVAL;
+            if tick {
+                last_tick = Instant::now();
+            }
+
+            let wakers: Vec<_> = self
+                .lock()
+                .pending
+                .iter_mut()
+                .filter(|(id, entry)| match entry.fd() {
+                    Some(_) => ready.contains(id),
+                    None => tick,
+                })
+                .filter_map(|(_, entry)| entry.changed().then(|| entry.waker.clone()))
+                .collect();
+            // Wakes outside of the lock, because the waker may poll the future in place.
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Interrupts the watcher thread, so it waits on the newly registered futures.
+struct Signal {
+    /// An eventfd, or `None` if it cannot be created, in which case the new futures are picked
+    /// up within `WATCH_INTERVAL`.
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    eventfd: Option<OwnedFd>,
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    notified: Mutex<bool>,
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    condvar: Condvar,
+}
+
+impl Signal {
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn new() -> Self {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        let eventfd = (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) });
+        Signal { eventfd }
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    fn new() -> Self {
+        Signal {
+            notified: Mutex::new(false),
+            condvar: Condvar::new(),
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn notify(&self) {
+        if let Some(eventfd) = &self.eventfd {
+            let one = 1u64;
+            let buf = &one as *const u64 as *const libc::c_void;
+            unsafe { libc::write(eventfd.as_raw_fd(), buf, std::mem::size_of::<u64>()) };
+        }
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    fn notify(&self) {
+        *self.notified.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.condvar.notify_one();
+    }
+
+    /// Blocks until any of `fds` becomes readable, the signal is notified, or `timeout` elapses,
+    /// and returns the ids of the readable file descriptors.
+    #[cfg(all(target_os = "linux", feature = "libc"))]
+    fn wait(&self, fds: &[(u64, Fd)], timeout: Option<Duration>) -> Vec<u64> {
+        let timeout = match self.eventfd {
+            Some(_) => timeout,
+            None => Some(timeout.map_or(WATCH_INTERVAL, |timeout| timeout.min(WATCH_INTERVAL))),
+        };
+        // Rounds up, or the thread spins until the last partial millisecond elapses.
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout
+                .as_nanos()
+                .div_ceil(1_000_000)
+                .min(libc::c_int::MAX as u128) as libc::c_int
+        });
+        let mut pollfds: Vec<_> = self
+            .eventfd
+            .iter()
+            .map(AsRawFd::as_raw_fd)
+            .chain(fds.iter().map(|(_, fd)| *fd))
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let len = pollfds.len() as libc::nfds_t;
+        if unsafe { libc::poll(pollfds.as_mut_ptr(), len, timeout) } <= 0 {
+            return Vec::new();
+        }
+
+        let skipped = match &self.eventfd {
+            Some(eventfd) => {
+                let mut counter = 0u64;
+                let buf = &mut counter as *mut u64 as *mut libc::c_void;
+                unsafe { libc::read(eventfd.as_raw_fd(), buf, std::mem::size_of::<u64>()) };
+                1
+            }
+            None => 0,
+        };
+        fds.iter()
+            .zip(&pollfds[skipped..])
+            .filter(|(_, pollfd)| pollfd.revents != 0)
+            .map(|((id, _), _)| *id)
+            .collect()
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "libc")))]
+    fn wait(&self, _fds: &[(u64, Fd)], timeout: Option<Duration>) -> Vec<u64> {
+        let notified = self.notified.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut notified = match timeout {
+            Some(timeout) => {
+                self.condvar
+                    .wait_timeout_while(notified, timeout, |notified| !*notified)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => self
+                .condvar
+                .wait_while(notified, |notified| !*notified)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        *notified = false;
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faketime::{enable, enable_millis, millis_tempfile, write_millis};
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct ThreadWaker {
+        thread: thread::Thread,
+        woken: AtomicUsize,
+    }
+
+    impl ThreadWaker {
+        fn new() -> Arc<Self> {
+            Arc::new(ThreadWaker {
+                thread: thread::current(),
+                woken: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.woken.fetch_add(1, Ordering::SeqCst);
+            self.thread.unpark();
+        }
+    }
+
+    #[test]
+    fn test_until_wakes_on_write() {
+        let faketime_file = millis_tempfile(1_000).expect("create faketime file");
+        enable(&faketime_file);
+        let mut first = pin!(until(2_000));
+        let mut second = pin!(until(2_000));
+        let waker = Waker::from(ThreadWaker::new());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Poll::Pending, first.as_mut().poll(&mut cx));
+        assert_eq!(Poll::Pending, second.as_mut().poll(&mut cx));
+
+        let path = faketime_file.to_path_buf();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            write_millis(&path, 2_000).expect("write millis");
+        });
+        let start = Instant::now();
+        while first.as_mut().poll(&mut cx).is_pending()
+            || second.as_mut().poll(&mut cx).is_pending()
+        {
+            assert!(start.elapsed() < Duration::from_secs(10), "not woken");
+            thread::park_timeout(Duration::from_secs(1));
+        }
+        writer.join().expect("join thread");
+        assert_eq!(Duration::from_millis(2_000), unix_time());
+    }
+
+    #[test]
+    fn test_until_wakes_periodically_in_memory() {
+        enable_millis(1_000);
+        let mut until = until(2_000);
+        let thread_waker = ThreadWaker::new();
+        let waker = Waker::from(Arc::clone(&thread_waker));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Poll::Pending, Pin::new(&mut until).poll(&mut cx));
+        let id = until.id.expect("registered");
+
+        let start = Instant::now();
+        while thread_waker.woken.load(Ordering::SeqCst) < 2 {
+            assert!(start.elapsed() < Duration::from_secs(10), "not woken");
+            thread::park_timeout(Duration::from_secs(1));
+        }
+        drop(until);
+        assert!(!watcher().lock().pending.contains_key(&id));
+    }
+}
@@ -1,5 +1,37 @@
+//! Provides the clocks of `std::time` for wasm32-unknown-unknown, where `std::time` panics.
+//!
+//! `SystemTime` is backed by `Date.now()`, which is millisecond-granular. `Instant` is backed by
+//! the high resolution and monotonic `performance.now()`, and falls back to `Date.now()` when the
+//! global `performance` object is unavailable.
+
+use js_sys::wasm_bindgen::{JsCast, JsValue};
+use js_sys::{Function, Reflect};
 use std::time::Duration;
 
+thread_local! {
+    /// The global `performance` object and its `now` method.
+    static PERFORMANCE: Option<(JsValue, Function)> = performance();
+}
+
+fn performance() -> Option<(JsValue, Function)> {
+    let performance = Reflect::get(&js_sys::global(), &JsValue::from_str("performance")).ok()?;
+    let now = Reflect::get(&performance, &JsValue::from_str("now"))
+        .ok()?
+        .dyn_into::<Function>()
+        .ok()?;
+    Some((performance, now))
+}
+
+/// Gets the milliseconds of `performance.now()`, or `Date.now()` as the fallback.
+fn performance_now() -> f64 {
+    PERFORMANCE
+        .with(|performance| {
+            let (performance, now) = performance.as_ref()?;
+            now.call0(performance).ok()?.as_f64()
+        })
+        .unwrap_or_else(js_sys::Date::now)
+}
+
 #[derive(Copy, Clone)]
 pub struct SystemTime(f64);
 
@@ -18,3 +50,21 @@ impl SystemTime {
         Ok(Duration::from_millis(dur_ms as u64))
     }
 }
+
+#[derive(Copy, Clone)]
+pub struct Instant(f64);
+
+impl Instant {
+    pub fn now() -> Instant {
+        Instant(performance_now())
+    }
+
+    /// Keeps the sub-millisecond precision of `performance.now()`.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        let dur_ms = self.0 - earlier.0;
+        if dur_ms.is_nan() || dur_ms < 0.0 {
+            return Duration::from_millis(0);
+        }
+        Duration::from_secs_f64(dur_ms / 1_000.0)
+    }
+}
@@ -0,0 +1,29 @@
+#![cfg(not(disable_faketime))]
+
+use std::time::Duration;
+
+#[test]
+fn bounds_should_clamp_all_modes() {
+    let min = Duration::from_secs(100);
+    let max = Duration::from_secs(200);
+    faketime::set_bounds(min, max);
+
+    let faketime_file = faketime::millis_tempfile(50_000).expect("create faketime file");
+    faketime::enable(&faketime_file);
+    assert_eq!(min, faketime::unix_time());
+    faketime::write_millis(&faketime_file, 150_000).expect("write millis");
+    assert_eq!(Duration::from_secs(150), faketime::unix_time());
+    faketime::write_millis(&faketime_file, 250_000).expect("write millis");
+    assert_eq!(max, faketime::unix_time());
+
+    faketime::enable_millis(50_000);
+    assert_eq!(min, faketime::unix_time());
+    faketime::enable_millis(250_000);
+    assert_eq!(max, faketime::unix_time());
+
+    faketime::disable();
+    assert_eq!(max, faketime::unix_time());
+
+    faketime::clear_bounds();
+    assert!(faketime::unix_time() > max);
+}
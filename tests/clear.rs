@@ -0,0 +1,58 @@
+#![cfg(not(disable_faketime))]
+
+use faketime::clock::FakeClock;
+use std::panic;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// The process wide states are cleared, tests in this file are serialized via the lock.
+static CLEAR_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_clear() -> MutexGuard<'static, ()> {
+    CLEAR_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn clear_all_should_restore_pristine_state() {
+    let _lock = lock_clear();
+    let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+    faketime::enable(&faketime_file);
+    faketime::enable_monotonic_file(&faketime_file);
+    faketime::set_global_path(&faketime_file);
+    faketime::add_env_key("faketime_test_clear");
+    faketime::set_bounds(Duration::from_secs(1), Duration::from_secs(2));
+    faketime::install_as_global_default(Arc::new(FakeClock::new(3_000)));
+    faketime::install_panic_dump();
+    assert!(faketime::poll_changes().is_some());
+
+    faketime::clear_all();
+    assert_eq!("disabled", faketime::describe());
+    assert_eq!(None, faketime::enabled_since());
+    let start = faketime::system::unix_time();
+    assert!(faketime::unix_time() - start < Duration::from_secs(60));
+    assert!(faketime::monotonic() < Duration::from_secs(60));
+    assert!(faketime::poll_changes().is_some());
+}
+
+#[test]
+fn reset_on_panic_should_clear_after_panic() {
+    let _lock = lock_clear();
+    let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+    let result = panic::catch_unwind(|| {
+        let _guard = faketime::reset_on_panic();
+        faketime::enable(&faketime_file);
+        faketime::set_bounds(Duration::from_secs(1), Duration::from_secs(2));
+        panic!("test failure");
+    });
+    assert!(result.is_err());
+    let start = faketime::system::unix_time();
+    assert!(faketime::unix_time() - start < Duration::from_secs(60));
+    assert_eq!("disabled", faketime::describe());
+
+    {
+        let _guard = faketime::reset_on_panic();
+        faketime::enable_millis(123_456);
+    }
+    assert_eq!(123_456, faketime::unix_time_as_millis());
+    faketime::clear_all();
+}
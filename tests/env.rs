@@ -0,0 +1,206 @@
+#![cfg(not(disable_faketime))]
+
+use std::env;
+use std::ffi::OsString;
+use std::panic;
+use std::sync::{mpsc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// Environment variables are process global, tests in this file are serialized via the lock.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_env() -> MutexGuard<'static, ()> {
+    ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn scoped_env_should_restore_faketime() {
+    let _lock = lock_env();
+    env::remove_var("FAKETIME");
+    {
+        let _guard = faketime::scoped_env("/tmp/faketime-outer");
+        assert_eq!(
+            Some(OsString::from("/tmp/faketime-outer")),
+            env::var_os("FAKETIME")
+        );
+        {
+            let _guard = faketime::scoped_env("/tmp/faketime-inner");
+            assert_eq!(
+                Some(OsString::from("/tmp/faketime-inner")),
+                env::var_os("FAKETIME")
+            );
+        }
+        assert_eq!(
+            Some(OsString::from("/tmp/faketime-outer")),
+            env::var_os("FAKETIME")
+        );
+    }
+    assert_eq!(None, env::var_os("FAKETIME"));
+}
+
+#[test]
+fn registered_env_key_should_be_detected() {
+    let _lock = lock_env();
+    let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+    let other_file = faketime::millis_tempfile(654_321).expect("create faketime file");
+    faketime::add_env_key("faketime_test_first");
+    faketime::add_env_key("faketime_test_second");
+    env::set_var("faketime_test_second", other_file.as_os_str());
+
+    thread::spawn(|| assert_eq!(654, faketime::unix_time().as_secs()))
+        .join()
+        .expect("join thread");
+
+    env::set_var("faketime_test_first", faketime_file.as_os_str());
+    thread::spawn(|| assert_eq!(123, faketime::unix_time().as_secs()))
+        .join()
+        .expect("join thread");
+
+    env::remove_var("faketime_test_first");
+    env::remove_var("faketime_test_second");
+}
+
+#[test]
+fn reset_all_should_redetect_in_all_threads() {
+    let _lock = lock_env();
+    let first_file = faketime::millis_tempfile(100_000).expect("create faketime file");
+    let second_file = faketime::millis_tempfile(200_000).expect("create faketime file");
+    env::set_var("FAKETIME", first_file.as_os_str());
+
+    let (to_worker, from_main) = mpsc::channel::<()>();
+    let (to_main, from_worker) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        while from_main.recv().is_ok() {
+            to_main
+                .send(faketime::unix_time().as_secs())
+                .expect("send time");
+        }
+    });
+    let read = || {
+        to_worker.send(()).expect("request time");
+        from_worker.recv().expect("receive time")
+    };
+
+    assert_eq!(100, read());
+    env::set_var("FAKETIME", second_file.as_os_str());
+    assert_eq!(100, read());
+    faketime::reset_all();
+    assert_eq!(200, read());
+
+    drop(to_worker);
+    worker.join().expect("join thread");
+    env::remove_var("FAKETIME");
+}
+
+#[test]
+fn faketime_monotonic_should_apply_floor() {
+    let _lock = lock_env();
+    let faketime_file = faketime::millis_tempfile(3_000).expect("create faketime file");
+    env::set_var("FAKETIME", faketime_file.as_os_str());
+    env::set_var("FAKETIME_MONOTONIC", "1");
+
+    faketime::reset();
+    let mut seen = Vec::new();
+    for millis in [3_000, 2_000, 1_000, 4_000, 3_500] {
+        faketime::write_millis(&faketime_file, millis).expect("write millis");
+        seen.push(faketime::unix_time_as_millis());
+    }
+    assert_eq!(vec![3_000, 3_000, 3_000, 4_000, 4_000], seen);
+
+    faketime::enable(&faketime_file);
+    faketime::write_millis(&faketime_file, 1_000).expect("write millis");
+    assert_eq!(1_000, faketime::unix_time_as_millis());
+
+    env::remove_var("FAKETIME_MONOTONIC");
+    env::remove_var("FAKETIME");
+    faketime::reset();
+}
+
+#[test]
+fn realtime_thread_should_use_system_time() {
+    let _lock = lock_env();
+    let faketime_file = faketime::millis_tempfile(123_456).expect("create faketime file");
+    let spawn = |name: String| {
+        thread::Builder::new()
+            .name(name)
+            .spawn(faketime::unix_time_as_millis)
+            .expect("spawn thread")
+            .join()
+            .expect("join thread")
+    };
+    let realtime = || spawn("REALTIME-worker".to_string());
+    let faketime = || spawn(format!("FAKETIME={}", faketime_file.display()));
+
+    assert_eq!(123_456, faketime());
+    assert!(realtime() > 123_456);
+
+    env::set_var("FAKETIME", faketime_file.as_os_str());
+    assert_eq!(123_456, faketime());
+    assert!(realtime() > 123_456);
+    env::remove_var("FAKETIME");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_path_should_be_detected() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let _lock = lock_env();
+    let tempdir = tempfile::tempdir().expect("create tempdir");
+    let faketime_file = tempdir
+        .path()
+        .join(OsStr::from_bytes(b"faketime-\xff"))
+        .join("faketime");
+    std::fs::create_dir(faketime_file.parent().expect("parent")).expect("create dir");
+    faketime::write_millis(&faketime_file, 123_456).expect("write millis");
+    assert!(faketime_file.to_str().is_none());
+
+    faketime::enable(faketime_file.as_os_str());
+    assert_eq!(123_456, faketime::unix_time_as_millis());
+
+    env::set_var("FAKETIME", &faketime_file);
+    let detected = thread::spawn(faketime::unix_time_as_millis)
+        .join()
+        .expect("join thread");
+    env::remove_var("FAKETIME");
+    assert_eq!(123_456, detected);
+}
+
+#[cfg(unix)]
+#[test]
+fn millis_tempfile_should_name_unavailable_temp_dir() {
+    let _lock = lock_env();
+    let previous = env::var_os("TMPDIR");
+    env::set_var("TMPDIR", "/path/to/missing/tmpdir");
+    let result = faketime::millis_tempfile(123);
+    match previous {
+        Some(val) => env::set_var("TMPDIR", val),
+        None => env::remove_var("TMPDIR"),
+    }
+
+    let message = result.expect_err("missing temp dir").to_string();
+    assert!(message.contains("/path/to/missing/tmpdir"), "{}", message);
+    assert!(message.contains("millis_tempfile_in"), "{}", message);
+}
+
+#[test]
+fn with_env_should_restore_after_panic() {
+    let _lock = lock_env();
+    env::set_var("FAKETIME_MILLIS", "1000");
+    let result = panic::catch_unwind(|| {
+        faketime::with_env("FAKETIME_MILLIS", "123456", || {
+            let secs = thread::spawn(|| faketime::unix_time().as_secs())
+                .join()
+                .expect("join thread");
+            assert_eq!(123, secs);
+            panic!("test failure");
+        })
+    });
+    assert!(result.is_err());
+    assert_eq!(Some(OsString::from("1000")), env::var_os("FAKETIME_MILLIS"));
+
+    env::remove_var("FAKETIME_MILLIS");
+    assert_eq!(1, faketime::with_env("FAKETIME_MILLIS", "1", || 1));
+    assert_eq!(None, env::var_os("FAKETIME_MILLIS"));
+}
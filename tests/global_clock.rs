@@ -0,0 +1,116 @@
+#![cfg(not(disable_faketime))]
+
+use faketime::clock::{ArcClock, Clock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Barrier, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// The global clock is process wide, tests in this file are serialized via the lock.
+static CLOCK_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_clock() -> MutexGuard<'static, ()> {
+    CLOCK_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn global_clock_should_override_thread_settings() {
+    let _lock = lock_clock();
+    let millis = Arc::new(AtomicU64::new(123_456));
+    let clock = ArcClock(Arc::clone(&millis));
+    faketime::install_as_global_default(Arc::new(clock.clone()));
+
+    thread::spawn(|| assert_eq!(Duration::from_millis(123_456), faketime::unix_time()))
+        .join()
+        .expect("join thread");
+
+    let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+    faketime::enable(&faketime_file);
+    millis.store(654_321, Ordering::SeqCst);
+    assert_eq!(clock.unix_time(), faketime::unix_time());
+}
+
+#[test]
+fn global_clock_should_be_replaced_while_reading() {
+    let _lock = lock_clock();
+    let install = |secs: u64| {
+        let millis = Arc::new(AtomicU64::new(secs * 1_000));
+        faketime::install_as_global_default(Arc::new(ArcClock(millis)));
+    };
+    install(999);
+    let readers: Vec<_> = (0..8)
+        .map(|_| {
+            thread::spawn(|| {
+                (0..10_000)
+                    .map(|_| faketime::unix_time())
+                    .all(|now| Duration::from_secs(999) <= now && now < Duration::from_secs(1_010))
+            })
+        })
+        .collect();
+    for secs in 1_000..1_010 {
+        install(secs);
+    }
+    for reader in readers {
+        assert!(reader.join().expect("join thread"));
+    }
+}
+
+/// Counts how many times the clock is dropped, and optionally blocks the readers until released.
+struct DropCounted {
+    dropped: Arc<AtomicU64>,
+    barrier: Option<Arc<Barrier>>,
+}
+
+impl Clock for DropCounted {
+    fn unix_time(&self) -> Duration {
+        if let Some(barrier) = &self.barrier {
+            barrier.wait();
+            barrier.wait();
+        }
+        Duration::from_secs(1)
+    }
+}
+
+impl Drop for DropCounted {
+    fn drop(&mut self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn replaced_clock_should_be_dropped() {
+    let _lock = lock_clock();
+    let dropped = Arc::new(AtomicU64::new(0));
+    for _ in 0..10 {
+        faketime::install_as_global_default(Arc::new(DropCounted {
+            dropped: Arc::clone(&dropped),
+            barrier: None,
+        }));
+        assert_eq!(Duration::from_secs(1), faketime::unix_time());
+    }
+    assert_eq!(9, dropped.load(Ordering::SeqCst));
+    faketime::clear_all();
+    assert_eq!(10, dropped.load(Ordering::SeqCst));
+}
+
+#[test]
+fn clock_being_read_should_be_dropped_after_reading() {
+    let _lock = lock_clock();
+    let dropped = Arc::new(AtomicU64::new(0));
+    let barrier = Arc::new(Barrier::new(2));
+    faketime::install_as_global_default(Arc::new(DropCounted {
+        dropped: Arc::clone(&dropped),
+        barrier: Some(Arc::clone(&barrier)),
+    }));
+    let reader = thread::spawn(faketime::unix_time);
+
+    barrier.wait();
+    faketime::clear_all();
+    assert_eq!(0, dropped.load(Ordering::SeqCst));
+    barrier.wait();
+    assert_eq!(Duration::from_secs(1), reader.join().expect("join thread"));
+    assert_eq!(0, dropped.load(Ordering::SeqCst));
+
+    faketime::clear_all();
+    assert_eq!(1, dropped.load(Ordering::SeqCst));
+}
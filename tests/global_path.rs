@@ -0,0 +1,32 @@
+#![cfg(not(disable_faketime))]
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+#[test]
+fn set_millis_all_threads_should_update_readers() {
+    assert!(faketime::set_millis_all_threads(1_000).is_err());
+
+    let faketime_file = faketime::millis_tempfile(1_000).expect("create faketime file");
+    faketime::set_global_path(&faketime_file);
+
+    let barrier = Arc::new(Barrier::new(5));
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                assert_eq!(1_000, faketime::unix_time_as_millis());
+                barrier.wait();
+                barrier.wait();
+                assert_eq!(2_000, faketime::unix_time_as_millis());
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    faketime::set_millis_all_threads(2_000).expect("set millis");
+    barrier.wait();
+    for reader in readers {
+        reader.join().expect("join thread");
+    }
+}
@@ -0,0 +1,47 @@
+#![cfg(not(disable_faketime))]
+
+use std::env;
+use std::panic;
+use std::process::Command;
+
+const KEY_CHILD: &str = "FAKETIME_TEST_CHILD";
+
+fn is_child() -> bool {
+    env::var_os(KEY_CHILD).is_some()
+}
+
+/// Runs the test `name` of this binary in a child process and returns its stderr.
+fn child_stderr(name: &str) -> String {
+    let output = Command::new(env::current_exe().expect("current exe"))
+        .args(["--exact", name, "--nocapture", "--test-threads=1"])
+        .env(KEY_CHILD, "1")
+        .output()
+        .expect("run child");
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn child_panic_with_dump() {
+    if is_child() {
+        faketime::install_panic_dump();
+        faketime::install_panic_dump();
+        faketime::enable_millis(123_456);
+        assert!(panic::catch_unwind(|| panic!("first")).is_err());
+
+        faketime::uninstall_panic_dump();
+        faketime::enable_millis(654_321);
+        assert!(panic::catch_unwind(|| panic!("second")).is_err());
+    }
+}
+
+#[test]
+fn panic_dump_should_print_settings() {
+    if is_child() {
+        return;
+    }
+    let stderr = child_stderr("child_panic_with_dump");
+    assert_eq!(1, stderr.matches("faketime: frozen at 123456ms").count());
+    assert!(!stderr.contains("654321"));
+    assert!(stderr.contains("first"));
+    assert!(stderr.contains("second"));
+}
@@ -0,0 +1,103 @@
+#![cfg(not(disable_faketime))]
+
+use std::env;
+use std::process::{Command, Output};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+const KEY_CHILD: &str = "FAKETIME_TEST_CHILD";
+
+/// Runs the test `name` of this binary in a child process. The test harness may print the test
+/// name in the same line before the output of the test.
+fn child_command(name: &str) -> Command {
+    let mut cmd = Command::new(env::current_exe().expect("current exe"));
+    cmd.args(["--exact", name, "--nocapture", "--test-threads=1"])
+        .env(KEY_CHILD, "1");
+    cmd
+}
+
+/// Spawning a child copies the environment of this process, which must not overlap with the tests
+/// mutating the environment, tests in this file are serialized via the lock.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_env() -> MutexGuard<'static, ()> {
+    ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn is_child() -> bool {
+    env::var_os(KEY_CHILD).is_some()
+}
+
+fn stdout_value(output: &Output, key: &str) -> Option<u64> {
+    let prefix = format!("{}=", key);
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let start = line.find(&prefix)? + prefix.len();
+            line[start..].parse().ok()
+        })
+}
+
+#[test]
+fn child_print_unix_time() {
+    if is_child() {
+        println!("unix_time={}", faketime::unix_time_as_millis());
+    }
+}
+
+#[test]
+fn command_env_should_export_frozen_time() {
+    if is_child() {
+        return;
+    }
+    let _lock = lock_env();
+    faketime::enable_millis(123_456);
+    let mut cmd = child_command("child_print_unix_time");
+    faketime::command_env(&mut cmd);
+    let output = cmd.output().expect("run child");
+    assert_eq!(Some(123_456), stdout_value(&output, "unix_time"));
+}
+
+#[test]
+fn command_env_should_export_timestamp_file() {
+    if is_child() {
+        return;
+    }
+    let _lock = lock_env();
+    let faketime_file = faketime::millis_tempfile(654_321).expect("create faketime file");
+    faketime::enable(&faketime_file);
+    let mut cmd = child_command("child_print_unix_time");
+    faketime::command_env(&mut cmd);
+    let output = cmd.output().expect("run child");
+    assert_eq!(Some(654_321), stdout_value(&output, "unix_time"));
+}
+
+#[test]
+fn command_env_should_export_disabled() {
+    if is_child() {
+        return;
+    }
+    let _lock = lock_env();
+    faketime::disable();
+    let mut cmd = child_command("child_print_unix_time");
+    cmd.env("FAKETIME_MILLIS", "123456");
+    faketime::command_env(&mut cmd);
+    let output = cmd.output().expect("run child");
+    let millis = stdout_value(&output, "unix_time").expect("child time");
+    assert!(millis > 1_000_000_000_000);
+}
+
+#[test]
+fn enable_millis_inheritable_should_be_inherited() {
+    if is_child() {
+        return;
+    }
+    let _lock = lock_env();
+    faketime::enable_millis_inheritable(234_567);
+    let output = child_command("child_print_unix_time")
+        .env_remove("FAKETIME")
+        .output()
+        .expect("run child");
+    env::remove_var("FAKETIME_MILLIS");
+    assert_eq!(Some(234_567), stdout_value(&output, "unix_time"));
+    assert_eq!(234_567, faketime::unix_time_as_millis());
+}
@@ -0,0 +1,68 @@
+#![cfg(all(feature = "tracing", not(disable_faketime)))]
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Captures the fields of events in the target `faketime` as text.
+#[derive(Default, Clone)]
+struct Capture(Arc<Mutex<Vec<String>>>);
+
+struct FieldsVisitor<'a>(&'a mut String);
+
+impl Visit for FieldsVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        self.0.push_str(&format!("{}={:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "faketime"
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = String::new();
+        event.record(&mut FieldsVisitor(&mut fields));
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(fields);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn mode_changes_should_be_traced() {
+    let capture = Capture::default();
+    tracing::subscriber::with_default(capture.clone(), || {
+        faketime::enable_millis(1_000);
+        faketime::advance(Duration::from_secs(1)).expect("advance");
+        faketime::disable();
+    });
+
+    let events = capture.0.lock().unwrap_or_else(PoisonError::into_inner);
+    let thread = format!("thread={:?}", std::thread::current().id());
+    assert_eq!(3, events.len(), "{:?}", events);
+    assert!(events.iter().all(|event| event.contains(&thread)));
+    assert!(events[0].contains("mode=frozen at 1000ms"), "{:?}", events);
+    assert!(events[1].contains("time=2s previous=1s"), "{:?}", events);
+    assert!(events[2].contains("mode=disabled"), "{:?}", events);
+}
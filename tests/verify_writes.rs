@@ -0,0 +1,32 @@
+#![cfg(not(disable_faketime))]
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The verification of writes is process wide, tests in this file are serialized via the lock.
+static VERIFY_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_verify() -> MutexGuard<'static, ()> {
+    VERIFY_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn verified_writes_should_read_back() {
+    let _lock = lock_verify();
+    let tempdir = tempfile::tempdir().expect("create tempdir");
+    let faketime_file = tempdir.path().join("faketime");
+
+    faketime::set_verify_writes(true);
+    let written = faketime::write_millis(&faketime_file, 123);
+    let checksummed = faketime::write_millis_checksummed(&faketime_file, 456);
+    let mut writer = faketime::Writer::new(&faketime_file).expect("create writer");
+    let set = writer.set(789);
+    faketime::set_verify_writes(false);
+
+    assert!(written.is_ok());
+    assert!(checksummed.is_ok());
+    assert!(set.is_ok());
+    assert_eq!(
+        789,
+        faketime::validate_file(&faketime_file).expect("valid file")
+    );
+}
@@ -1,4 +1,5 @@
 use faketime::unix_time_as_millis;
+use std::time::Duration;
 use wasm_bindgen_test::*;
 wasm_bindgen_test_configure!(run_in_browser);
 
@@ -6,3 +7,19 @@ wasm_bindgen_test_configure!(run_in_browser);
 fn unix_time_as_millis_should_work() {
     assert!(unix_time_as_millis() > 0);
 }
+
+#[wasm_bindgen_test]
+fn system_monotonic_should_increase() {
+    let start = faketime::system::monotonic();
+    let mut previous = start;
+    for _ in 0..1_000_000 {
+        let now = faketime::system::monotonic();
+        assert!(now >= previous);
+        previous = now;
+        if now > start {
+            break;
+        }
+    }
+    assert!(previous > start);
+    assert!(previous - start < Duration::from_secs(60));
+}
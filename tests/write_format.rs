@@ -0,0 +1,63 @@
+#![cfg(not(disable_faketime))]
+
+use faketime::WriteFormat;
+use std::env;
+use std::fs;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The write format is process wide, tests in this file are serialized via the lock.
+static FORMAT_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_format() -> MutexGuard<'static, ()> {
+    FORMAT_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn write_format_should_be_read_back() {
+    let _lock = lock_format();
+    let millis = 1_577_880_000_500;
+    let faketime_file = faketime::millis_tempfile(0).expect("create faketime file");
+    faketime::enable(&faketime_file);
+
+    for (format, expected) in [
+        (WriteFormat::Millis, "1577880000500"),
+        (WriteFormat::Seconds, "s:1577880000.5"),
+        (WriteFormat::Rfc3339, "2020-01-01T12:00:00.500Z"),
+        (WriteFormat::NanosPrefixed, "ns:1577880000500000000"),
+    ] {
+        faketime::set_write_format(format);
+        faketime::write_millis(&faketime_file, millis).expect("write millis");
+        assert_eq!(
+            expected,
+            fs::read_to_string(&faketime_file).expect("read file")
+        );
+        assert_eq!(millis, faketime::unix_time_as_millis());
+
+        faketime::set_millis(millis + 1_000).expect("set millis");
+        assert_eq!(millis + 1_000, faketime::unix_time_as_millis());
+    }
+    faketime::set_write_format(WriteFormat::Millis);
+}
+
+#[test]
+fn write_format_should_default_to_env() {
+    let _lock = lock_format();
+    faketime::clear_all();
+    env::set_var("FAKETIME_FMT", "seconds");
+    let faketime_file = faketime::millis_tempfile(1_500).expect("create faketime file");
+    env::remove_var("FAKETIME_FMT");
+    assert_eq!(
+        "s:1.5",
+        fs::read_to_string(&faketime_file).expect("read file")
+    );
+
+    env::set_var("FAKETIME_FMT", "seconds");
+    faketime::set_write_format(WriteFormat::NanosPrefixed);
+    faketime::write_millis(&faketime_file, 1).expect("write millis");
+    env::remove_var("FAKETIME_FMT");
+    faketime::set_write_format(WriteFormat::Millis);
+    assert_eq!(
+        "ns:1000000",
+        fs::read_to_string(&faketime_file).expect("read file")
+    );
+}